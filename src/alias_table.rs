@@ -0,0 +1,105 @@
+use crate::Float;
+
+// Vose's alias method: constant-time sampling of an index from a discrete distribution.
+pub struct AliasTable {
+    probabilities: Vec<Float>,
+    thresholds: Vec<Float>,
+    aliases: Vec<usize>,
+}
+
+impl AliasTable {
+    pub fn new(weights: &[Float]) -> Self {
+        let n = weights.len();
+        let total: Float = weights.iter().sum();
+        let probabilities: Vec<Float> = if total > 0.0 {
+            weights.iter().map(|w| w / total).collect()
+        } else {
+            vec![1.0 / n as Float; n]
+        };
+
+        let mut scaled: Vec<Float> = probabilities.iter().map(|p| p * n as Float).collect();
+        let mut thresholds = vec![1.0; n];
+        let mut aliases: Vec<usize> = (0..n).collect();
+        let (mut small, mut large): (Vec<usize>, Vec<usize>) =
+            (0..n).partition(|&i| scaled[i] < 1.0);
+
+        while let (Some(s), Some(&l)) = (small.pop(), large.last()) {
+            thresholds[s] = scaled[s];
+            aliases[s] = l;
+            scaled[l] -= 1.0 - scaled[s];
+            if scaled[l] < 1.0 {
+                large.pop();
+                small.push(l);
+            }
+        }
+
+        Self {
+            probabilities,
+            thresholds,
+            aliases,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.probabilities.is_empty()
+    }
+
+    pub fn probability(&self, index: usize) -> Float {
+        self.probabilities[index]
+    }
+
+    // Maps a uniform number in [0, 1) to an index.
+    pub fn sample(&self, u: Float) -> usize {
+        let n = self.probabilities.len();
+        let scaled = u * n as Float;
+        let index = (scaled as usize).min(n - 1);
+        if scaled - (index as Float) < self.thresholds[index] {
+            index
+        } else {
+            self.aliases[index]
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // How often each index is drawn for evenly spaced uniform numbers.
+    fn frequencies(table: &AliasTable, n: usize) -> Vec<Float> {
+        let draws = 100_000;
+        let mut counts = vec![0; n];
+        for i in 0..draws {
+            counts[table.sample((i as Float + 0.5) / draws as Float)] += 1;
+        }
+        counts
+            .into_iter()
+            .map(|count| count as Float / draws as Float)
+            .collect()
+    }
+
+    #[test]
+    fn samples_follow_the_weights() {
+        let weights = [3.0, 0.0, 1.0, 0.5, 7.25, 0.25];
+        let table = AliasTable::new(&weights);
+        let total: Float = weights.iter().sum();
+        for (i, frequency) in frequencies(&table, weights.len()).into_iter().enumerate() {
+            assert!((table.probability(i) - weights[i] / total).abs() < 1.0e-6);
+            assert!(
+                (frequency - table.probability(i)).abs() < 1.0e-3,
+                "Index {} is drawn with frequency {} instead of {}",
+                i,
+                frequency,
+                table.probability(i)
+            );
+        }
+    }
+
+    #[test]
+    fn zero_weights_are_uniform() {
+        let table = AliasTable::new(&[0.0; 4]);
+        for frequency in frequencies(&table, 4) {
+            assert!((frequency - 0.25).abs() < 1.0e-3);
+        }
+    }
+}
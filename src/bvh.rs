@@ -23,6 +23,15 @@ impl<'a> Debug for BVHValue<'a> {
     }
 }
 
+impl<'a> BVHValue<'a> {
+    fn emitters(&self) -> Vec<&dyn Hittable> {
+        match self {
+            Self::SubBVH(bvh) => bvh.emitters(),
+            Self::Leaf(leaf) => leaf.emitters(),
+        }
+    }
+}
+
 pub struct BVH<'a> {
     left: BVHValue<'a>,
     right: BVHValue<'a>,
@@ -146,6 +155,19 @@ impl<'a> Hittable for BVH<'a> {
     fn get_name(&self) -> &String {
         return &self.name;
     }
+
+    fn emitters(&self) -> Vec<&dyn Hittable> {
+        let mut emitters = self.left.emitters();
+        // A single object is stored on both sides.
+        let duplicate = match (&self.left, &self.right) {
+            (BVHValue::Leaf(left), BVHValue::Leaf(right)) => std::ptr::addr_eq(*left, *right),
+            _ => false,
+        };
+        if !duplicate {
+            emitters.extend(self.right.emitters());
+        }
+        emitters
+    }
 }
 
 impl<'a> Debug for BVH<'a> {
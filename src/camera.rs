@@ -45,6 +45,53 @@ impl Camera {
         cam
     }
 
+    // Area of the image plane placed at unit distance from the pinhole.
+    fn film_area(&self) -> Float {
+        let width = self.pixel_delta_u.length() * self.image_width as Float;
+        let height = self.pixel_delta_v.length() * self.image_height as Float;
+        width * height / (self.focus_distance * self.focus_distance)
+    }
+
+    pub fn raster_position(&self, p: Vec3) -> Option<(i32, i32)> {
+        let direction = (p - self.center).normalize();
+        let cos_theta = direction.dot(-self.w);
+        if cos_theta <= 0.0 {
+            return None;
+        }
+
+        let on_plane = self.center + direction * (self.focus_distance / cos_theta);
+        let from_corner =
+            on_plane - self.pixel00_loc + 0.5 * (self.pixel_delta_u + self.pixel_delta_v);
+        let x = from_corner.dot(self.pixel_delta_u) / self.pixel_delta_u.length_squared();
+        let y = from_corner.dot(self.pixel_delta_v) / self.pixel_delta_v.length_squared();
+        if x < 0.0 || y < 0.0 || x >= self.image_width as Float || y >= self.image_height as Float {
+            return None;
+        }
+
+        Some((x as i32, y as i32))
+    }
+
+    pub fn importance(&self, direction: Vec3) -> Float {
+        let cos_theta = direction.normalize().dot(-self.w);
+        if cos_theta <= 0.0 {
+            return 0.0;
+        }
+        let cos2_theta = cos_theta * cos_theta;
+        1.0 / (self.film_area() * cos2_theta * cos2_theta)
+    }
+
+    pub fn direction_pdf(&self, direction: Vec3) -> Float {
+        let cos_theta = direction.normalize().dot(-self.w);
+        if cos_theta <= 0.0 {
+            return 0.0;
+        }
+        1.0 / (self.film_area() * cos_theta * cos_theta * cos_theta)
+    }
+
+    pub fn forward(&self) -> Vec3 {
+        -self.w
+    }
+
     fn initialize(&mut self) {
         self.image_height = (self.image_width as Float / self.aspect_ratio) as i32;
         self.image_height = if self.image_height < 1 {
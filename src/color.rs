@@ -39,6 +39,26 @@ impl Color {
         }
     }
 
+    pub fn r(&self) -> Float {
+        self.0.x
+    }
+
+    pub fn g(&self) -> Float {
+        self.0.y
+    }
+
+    pub fn b(&self) -> Float {
+        self.0.z
+    }
+
+    pub fn luminance(&self) -> Float {
+        0.2126 * self.0.x + 0.7152 * self.0.y + 0.0722 * self.0.z
+    }
+
+    pub fn is_black(&self) -> bool {
+        self.0 == Vec3::ZERO
+    }
+
     pub fn clamp(&self) -> Self {
         Color(self.0.clamp(Vec3::ZERO, Vec3::ONE))
     }
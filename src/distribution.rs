@@ -0,0 +1,103 @@
+use crate::{Float, Vec2};
+
+// A piecewise constant function over [0, 1) that can be sampled by inverting its CDF.
+pub struct Distribution1D {
+    function: Vec<Float>,
+    cdf: Vec<Float>,
+    integral: Float,
+}
+
+impl Distribution1D {
+    pub fn new(function: Vec<Float>) -> Self {
+        let n = function.len() as Float;
+        let mut cdf = Vec::with_capacity(function.len() + 1);
+        cdf.push(0.0);
+        for value in &function {
+            cdf.push(cdf[cdf.len() - 1] + value.max(0.0) / n);
+        }
+        let integral = cdf[cdf.len() - 1];
+        // A function that is zero everywhere is sampled uniformly.
+        for (i, c) in cdf.iter_mut().enumerate() {
+            *c = if integral > 0.0 {
+                *c / integral
+            } else {
+                i as Float / n
+            };
+        }
+
+        Self {
+            function,
+            cdf,
+            integral,
+        }
+    }
+
+    pub fn integral(&self) -> Float {
+        self.integral
+    }
+
+    // Maps a uniform number to a point in [0, 1), returning it with its density and segment.
+    pub fn sample(&self, u: Float) -> (Float, Float, usize) {
+        let n = self.function.len();
+        let index = (self.cdf.partition_point(|&c| c <= u).max(1) - 1).min(n - 1);
+        let width = self.cdf[index + 1] - self.cdf[index];
+        let offset = if width > 0.0 {
+            (u - self.cdf[index]) / width
+        } else {
+            0.5
+        };
+        // Stay clear of the segment edges so that rounding cannot move `x` to a neighbour.
+        let x = (index as Float + offset.clamp(1.0e-3, 1.0 - 1.0e-3)) / n as Float;
+        (x, self.segment_pdf(index), index)
+    }
+
+    pub fn pdf(&self, x: Float) -> Float {
+        let n = self.function.len();
+        self.segment_pdf(((x * n as Float) as usize).min(n - 1))
+    }
+
+    fn segment_pdf(&self, index: usize) -> Float {
+        if self.integral > 0.0 {
+            self.function[index].max(0.0) / self.integral
+        } else {
+            1.0
+        }
+    }
+}
+
+// A piecewise constant function over the unit square, sampled through the marginal density of
+// its rows and the conditional density within the chosen row.
+pub struct Distribution2D {
+    conditionals: Vec<Distribution1D>,
+    marginal: Distribution1D,
+}
+
+impl Distribution2D {
+    // `function` holds `height` rows of `width` values each.
+    pub fn new(function: &[Float], width: usize, height: usize) -> Self {
+        let conditionals: Vec<Distribution1D> = function
+            .chunks(width)
+            .take(height)
+            .map(|row| Distribution1D::new(row.to_vec()))
+            .collect();
+        let marginal = Distribution1D::new(conditionals.iter().map(|c| c.integral()).collect());
+
+        Self {
+            conditionals,
+            marginal,
+        }
+    }
+
+    // Returns a point of the unit square and its density there.
+    pub fn sample(&self, u: Vec2) -> (Vec2, Float) {
+        let (y, marginal_pdf, row) = self.marginal.sample(u.y);
+        let (x, conditional_pdf, _) = self.conditionals[row].sample(u.x);
+        (Vec2::new(x, y), marginal_pdf * conditional_pdf)
+    }
+
+    pub fn pdf(&self, p: Vec2) -> Float {
+        let rows = self.conditionals.len();
+        let row = ((p.y * rows as Float) as usize).min(rows - 1);
+        self.marginal.pdf(p.y) * self.conditionals[row].pdf(p.x)
+    }
+}
@@ -0,0 +1,207 @@
+use std::ops::{Add, Div, Mul, Sub};
+
+use crate::{
+    color::Color,
+    spectrum::{cie_xyz, xyz_to_rgb},
+    Float, Vec3, PI,
+};
+
+// Schlick's approximation from the reflectance `f0` at normal incidence.
+pub fn schlick(f0: Color, cos_theta: Float) -> Color {
+    let m = (1.0 - cos_theta.clamp(0.0, 1.0)).powi(5);
+    f0 * (1.0 - m) + Color::new(m, m, m)
+}
+
+// Unpolarised reflectance of a conductor with complex index of refraction `eta + i k` per
+// channel, seen from outside at ior one.
+pub fn conductor(cos_theta: Float, eta: Color, k: Color) -> Color {
+    let cos = cos_theta.clamp(0.0, 1.0);
+    let cos2 = cos * cos;
+    let sin2 = 1.0 - cos2;
+    let channel = |eta: Float, k: Float| {
+        let t0 = eta * eta - k * k - sin2;
+        let a2b2 = (t0 * t0 + 4.0 * eta * eta * k * k).sqrt();
+        let a = (0.5 * (a2b2 + t0)).max(0.0).sqrt();
+        let t1 = a2b2 + cos2;
+        let t2 = 2.0 * a * cos;
+        let rs = (t1 - t2) / (t1 + t2);
+        let t3 = cos2 * a2b2 + sin2 * sin2;
+        let t4 = t2 * sin2;
+        let rp = rs * (t3 - t4) / (t3 + t4);
+        0.5 * (rs + rp)
+    };
+    Color::new(
+        channel(eta.r(), k.r()),
+        channel(eta.g(), k.g()),
+        channel(eta.b(), k.b()),
+    )
+}
+
+// Unpolarised reflectance of the boundary into a dielectric of relative index of refraction
+// `eta`, from the side `cos_theta` is positive on. Total internal reflection gives one.
+pub fn dielectric(cos_theta: Float, eta: Float) -> Float {
+    let (cos_i, eta) = if cos_theta < 0.0 {
+        (-cos_theta, 1.0 / eta)
+    } else {
+        (cos_theta, eta)
+    };
+    let cos_i = cos_i.min(1.0);
+    let sin2_t = (1.0 - cos_i * cos_i) / (eta * eta);
+    if sin2_t >= 1.0 {
+        return 1.0;
+    }
+    let cos_t = (1.0 - sin2_t).sqrt();
+    let parallel = (eta * cos_i - cos_t) / (eta * cos_i + cos_t);
+    let perpendicular = (cos_i - eta * cos_t) / (cos_i + eta * cos_t);
+    (parallel * parallel + perpendicular * perpendicular) / 2.0
+}
+
+// Unpolarised reflectance at wavelength `lambda` of a film `thickness` nanometres thick and of
+// index of refraction `film_ior`, lying on a substrate of complex index `eta + i k`, seen from a
+// medium of index `outside`. Light bouncing inside the film interferes with itself.
+pub fn thin_film(
+    cos_theta: Float,
+    outside: Float,
+    film_ior: Float,
+    thickness: Float,
+    eta: Float,
+    k: Float,
+    lambda: Float,
+) -> Float {
+    let cos = cos_theta.clamp(1.0e-6, 1.0);
+    let sin2 = (1.0 - cos * cos) * outside * outside;
+    let n1 = Complex::real(outside);
+    let n2 = Complex::real(film_ior);
+    let n3 = Complex::new(eta, k);
+    // The index times the cosine of the angle to the normal inside each layer.
+    let q1 = Complex::real(outside * cos);
+    let q = |n: Complex| (n * n - Complex::real(sin2)).sqrt();
+    let (q2, q3) = (q(n2), q(n3));
+
+    let perpendicular = |qa: Complex, qb: Complex| (qa - qb) / (qa + qb);
+    let parallel = |na: Complex, qa: Complex, nb: Complex, qb: Complex| {
+        (nb * nb * qa - na * na * qb) / (nb * nb * qa + na * na * qb)
+    };
+    let phase = (q2 * Complex::real(4.0 * PI * thickness.max(0.0) / lambda)).exp_i();
+    let airy = |r12: Complex, r23: Complex| {
+        ((r12 + r23 * phase) / (Complex::real(1.0) + r12 * r23 * phase)).norm_squared()
+    };
+    let rs = airy(perpendicular(q1, q2), perpendicular(q2, q3));
+    let rp = airy(parallel(n1, q1, n2, q2), parallel(n2, q2, n3, q3));
+    (0.5 * (rs + rp)).clamp(0.0, 1.0)
+}
+
+// `thin_film` over the visible spectrum seen as a colour. The substrate's index of refraction in
+// each channel is spread over the spectrum from the red, green and blue wavelengths.
+pub fn thin_film_rgb(
+    cos_theta: Float,
+    outside: Float,
+    film_ior: Float,
+    thickness: Float,
+    eta: Color,
+    k: Color,
+) -> Color {
+    let (xyz, white) = (0..=40).fold((Vec3::ZERO, Vec3::ZERO), |(xyz, white), i| {
+        let lambda = 380.0 + 10.0 * i as Float;
+        let reflectance = thin_film(
+            cos_theta,
+            outside,
+            film_ior,
+            thickness,
+            channel_at(eta, lambda),
+            channel_at(k, lambda),
+            lambda,
+        );
+        let cmf = cie_xyz(lambda);
+        (xyz + cmf * reflectance, white + cmf)
+    });
+    let (rgb, white) = (xyz_to_rgb(xyz), xyz_to_rgb(white));
+    Color::new(
+        (rgb.r() / white.r()).min(1.0),
+        (rgb.g() / white.g()).min(1.0),
+        (rgb.b() / white.b()).min(1.0),
+    )
+}
+
+// Value at `lambda` of a quantity known at the red, green and blue wavelengths.
+pub fn channel_at(c: Color, lambda: Float) -> Float {
+    let (blue, green, red) = (450.0, 550.0, 650.0);
+    if lambda <= blue {
+        c.b()
+    } else if lambda <= green {
+        c.b() + (c.g() - c.b()) * (lambda - blue) / (green - blue)
+    } else if lambda <= red {
+        c.g() + (c.r() - c.g()) * (lambda - green) / (red - green)
+    } else {
+        c.r()
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Complex {
+    re: Float,
+    im: Float,
+}
+
+impl Complex {
+    fn new(re: Float, im: Float) -> Self {
+        Self { re, im }
+    }
+
+    fn real(re: Float) -> Self {
+        Self::new(re, 0.0)
+    }
+
+    fn norm_squared(self) -> Float {
+        self.re * self.re + self.im * self.im
+    }
+
+    // The principal square root, whose real part is never negative.
+    fn sqrt(self) -> Self {
+        let norm = self.norm_squared().sqrt();
+        let re = (0.5 * (norm + self.re)).max(0.0).sqrt();
+        let im = (0.5 * (norm - self.re)).max(0.0).sqrt();
+        Self::new(re, if self.im < 0.0 { -im } else { im })
+    }
+
+    // e raised to i times this.
+    fn exp_i(self) -> Self {
+        let magnitude = (-self.im).exp();
+        Self::new(magnitude * self.re.cos(), magnitude * self.re.sin())
+    }
+}
+
+impl Add for Complex {
+    type Output = Complex;
+    fn add(self, rhs: Self) -> Self::Output {
+        Complex::new(self.re + rhs.re, self.im + rhs.im)
+    }
+}
+
+impl Sub for Complex {
+    type Output = Complex;
+    fn sub(self, rhs: Self) -> Self::Output {
+        Complex::new(self.re - rhs.re, self.im - rhs.im)
+    }
+}
+
+impl Mul for Complex {
+    type Output = Complex;
+    fn mul(self, rhs: Self) -> Self::Output {
+        Complex::new(
+            self.re * rhs.re - self.im * rhs.im,
+            self.re * rhs.im + self.im * rhs.re,
+        )
+    }
+}
+
+impl Div for Complex {
+    type Output = Complex;
+    fn div(self, rhs: Self) -> Self::Output {
+        let denom = rhs.norm_squared();
+        Complex::new(
+            (self.re * rhs.re + self.im * rhs.im) / denom,
+            (self.im * rhs.re - self.re * rhs.im) / denom,
+        )
+    }
+}
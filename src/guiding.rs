@@ -0,0 +1,147 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::{aabb::AABB, pdf::PDF, renderers::film::atomic_add, sampler::random, Float, Vec3, PI};
+
+const THETA_BINS: usize = 8;
+const PHI_BINS: usize = 16;
+const BINS: usize = THETA_BINS * PHI_BINS;
+
+// Share of guided samples that ignores the learned histogram, so no direction is starved by a noisy one.
+const UNIFORM_FRACTION: Float = 0.1;
+
+// Bins are equal-area cells of the cylindrical (cos theta, phi) parameterization of the sphere.
+fn bin_of(direction: Vec3) -> usize {
+    let d = direction.normalize();
+    let phi = Float::atan2(d.y, d.x) + PI;
+    let t = (((d.z + 1.0) * 0.5 * THETA_BINS as Float) as usize).min(THETA_BINS - 1);
+    let p = ((phi / (2.0 * PI) * PHI_BINS as Float) as usize).min(PHI_BINS - 1);
+    t * PHI_BINS + p
+}
+
+fn bin_solid_angle() -> Float {
+    4.0 * PI / BINS as Float
+}
+
+fn bin_center(bin: usize) -> Vec3 {
+    let z = -1.0 + 2.0 * ((bin / PHI_BINS) as Float + 0.5) / THETA_BINS as Float;
+    let phi = 2.0 * PI * ((bin % PHI_BINS) as Float + 0.5) / PHI_BINS as Float - PI;
+    let r = Float::sqrt((1.0 - z * z).max(0.0));
+    Vec3::new(r * Float::cos(phi), r * Float::sin(phi), z)
+}
+
+fn cell_of(aabb: &AABB, resolution: usize, p: Vec3) -> usize {
+    let extent = (aabb.max() - aabb.min()).max(Vec3::splat(1.0e-4));
+    let local = ((p - aabb.min()) / extent * resolution as Float)
+        .clamp(Vec3::ZERO, Vec3::splat((resolution - 1) as Float));
+    let (x, y, z) = (local.x as usize, local.y as usize, local.z as usize);
+    (z * resolution + y) * resolution + x
+}
+
+pub struct DirectionalDistribution {
+    cdf: [Float; BINS],
+}
+
+impl DirectionalDistribution {
+    fn bin_probability(&self, bin: usize) -> Float {
+        let previous = if bin == 0 { 0.0 } else { self.cdf[bin - 1] };
+        self.cdf[bin] - previous
+    }
+}
+
+impl PDF for DirectionalDistribution {
+    fn value(&self, direction: &Vec3) -> Float {
+        self.bin_probability(bin_of(*direction)) / bin_solid_angle()
+    }
+
+    fn generate(&self) -> Option<Vec3> {
+        let u = random();
+        let bin = self.cdf.partition_point(|&c| c <= u).min(BINS - 1);
+
+        let z = -1.0 + 2.0 * ((bin / PHI_BINS) as Float + random()) / THETA_BINS as Float;
+        let phi = 2.0 * PI * ((bin % PHI_BINS) as Float + random()) / PHI_BINS as Float - PI;
+        let r = Float::sqrt((1.0 - z * z).max(0.0));
+        Some(Vec3::new(r * Float::cos(phi), r * Float::sin(phi), z))
+    }
+}
+
+// Learned incident radiance: one normalized directional histogram per voxel of the scene bounds.
+pub struct GuidingField {
+    aabb: AABB,
+    resolution: usize,
+    cells: Vec<Option<[Float; BINS]>>,
+    centers: [Vec3; BINS],
+}
+
+impl GuidingField {
+    // The histogram of the voxel around `p`, restricted to the hemisphere above `normal` and weighted by the cosine.
+    pub fn distribution(&self, p: Vec3, normal: Vec3) -> Option<DirectionalDistribution> {
+        let histogram = self.cells[cell_of(&self.aabb, self.resolution, p)].as_ref()?;
+        let mut cdf = [0.0; BINS];
+        let mut total = 0.0;
+        for ((c, h), center) in cdf.iter_mut().zip(histogram).zip(&self.centers) {
+            let cos_theta = normal.dot(*center).max(0.0);
+            total += ((1.0 - UNIFORM_FRACTION) * h + UNIFORM_FRACTION / BINS as Float) * cos_theta;
+            *c = total;
+        }
+        if total <= 0.0 {
+            return None;
+        }
+        cdf.iter_mut().for_each(|c| *c /= total);
+        Some(DirectionalDistribution { cdf })
+    }
+}
+
+// Collects radiance samples from a rendering pass to train the next field.
+pub struct GuidingRecorder {
+    aabb: AABB,
+    resolution: usize,
+    bins: Vec<AtomicU64>,
+}
+
+impl GuidingRecorder {
+    pub fn new(aabb: AABB, resolution: usize) -> Self {
+        let resolution = resolution.max(1);
+        let bins = (0..resolution * resolution * resolution * BINS)
+            .map(|_| AtomicU64::new(0.0f64.to_bits()))
+            .collect();
+        Self {
+            aabb,
+            resolution,
+            bins,
+        }
+    }
+
+    pub fn record(&self, p: Vec3, direction: Vec3, weight: Float) {
+        if !weight.is_finite() || weight <= 0.0 {
+            return;
+        }
+        let cell = cell_of(&self.aabb, self.resolution, p);
+        atomic_add(&self.bins[cell * BINS + bin_of(direction)], weight as f64);
+    }
+
+    pub fn build(&self) -> GuidingField {
+        let cells = self
+            .bins
+            .chunks(BINS)
+            .map(|bins| {
+                let mut histogram = [0.0; BINS];
+                for (h, bin) in histogram.iter_mut().zip(bins) {
+                    *h = f64::from_bits(bin.load(Ordering::Relaxed)) as Float;
+                }
+                let total: Float = histogram.iter().sum();
+                if total <= 0.0 {
+                    return None;
+                }
+                histogram.iter_mut().for_each(|h| *h /= total);
+                Some(histogram)
+            })
+            .collect();
+
+        GuidingField {
+            aabb: self.aabb,
+            resolution: self.resolution,
+            cells,
+            centers: std::array::from_fn(bin_center),
+        }
+    }
+}
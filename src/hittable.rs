@@ -1,6 +1,7 @@
 use crate::interval::Interval;
 use crate::materials::material::Material;
 use crate::ray::Ray;
+use crate::sampler::random;
 use crate::Float;
 use crate::Vec2;
 use crate::{aabb::AABB, Vec3};
@@ -48,6 +49,19 @@ impl<'a> HitRecord<'a> {
     }
 }
 
+pub struct SurfaceSample {
+    pub p: Vec3,
+    pub normal: Vec3,
+    pub pdf: Float,
+}
+
+// Bounds on the directions of the surface normals, as an axis and the cosine of the spread around it.
+#[derive(Clone, Copy, Debug)]
+pub struct NormalCone {
+    pub axis: Vec3,
+    pub cos_theta: Float,
+}
+
 pub trait Hittable: Send + Sync + Debug {
     fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord>;
     fn bounding_box(&self) -> AABB;
@@ -58,6 +72,26 @@ pub trait Hittable: Send + Sync + Debug {
     fn random_vector_to_surface(&self, _origin: &Vec3) -> Vec3 {
         Vec3::X
     }
+    fn sample_surface(&self) -> Option<SurfaceSample> {
+        None
+    }
+    fn surface_pdf_value(&self, _origin: &Vec3, _direction: &Vec3) -> Float {
+        0.0
+    }
+    fn normal_cone(&self) -> NormalCone {
+        NormalCone {
+            axis: Vec3::Z,
+            cos_theta: -1.0,
+        }
+    }
+    // Objects with an emissive material, found by walking aggregates down to their leaves.
+    fn emitters(&self) -> Vec<&dyn Hittable> {
+        vec![]
+    }
+    // Aggregates holding nothing, which `random_vector_to_surface` cannot aim at.
+    fn is_empty(&self) -> bool {
+        false
+    }
 }
 
 pub struct HittableList<'a> {
@@ -117,13 +151,40 @@ impl<'a> Hittable for HittableList<'a> {
     }
 
     fn random_vector_to_surface(&self, origin: &Vec3) -> Vec3 {
-        let random_index = (rand::random::<Float>() * self.objects.len() as Float).floor();
+        let random_index = (random() * self.objects.len() as Float).floor();
         let picked = self
             .objects
             .get(random_index as usize)
             .expect("Random index out of bounds");
         picked.random_vector_to_surface(origin)
     }
+
+    fn sample_surface(&self) -> Option<SurfaceSample> {
+        let random_index = (random() * self.objects.len() as Float).floor();
+        let picked = self.objects.get(random_index as usize)?;
+        picked.sample_surface().map(|sample| SurfaceSample {
+            pdf: sample.pdf / self.objects.len() as Float,
+            ..sample
+        })
+    }
+
+    fn surface_pdf_value(&self, origin: &Vec3, direction: &Vec3) -> Float {
+        self.objects
+            .iter()
+            .fold(0.0, |acc, o| acc + o.surface_pdf_value(origin, direction))
+            / self.objects.len() as Float
+    }
+
+    fn emitters(&self) -> Vec<&dyn Hittable> {
+        self.objects
+            .iter()
+            .flat_map(|object| object.emitters())
+            .collect()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.objects.is_empty()
+    }
 }
 
 impl<'a> Debug for HittableList<'a> {
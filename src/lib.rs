@@ -1,12 +1,21 @@
 pub mod aabb;
+pub mod alias_table;
 pub mod bvh;
 pub mod camera;
 pub mod color;
+pub mod distribution;
+pub mod fresnel;
+pub mod guiding;
 pub mod hittable;
 pub mod image_writer;
 pub mod interval;
+pub mod light_list;
+pub mod light_tree;
+pub mod lights;
 pub mod materials;
+pub mod medium;
 pub mod mesh;
+pub mod microfacet;
 pub mod onb;
 pub mod pdf;
 pub mod quad;
@@ -14,10 +23,14 @@ pub mod rand_vec3;
 pub mod ray;
 pub mod render_parameters;
 pub mod renderers;
+pub mod sampler;
+pub mod scene;
+pub mod spectrum;
 pub mod sphere;
 pub mod texture;
 pub mod triangle;
 pub mod vertex;
+pub mod voxel_grid;
 
 #[cfg(not(feature = "f64"))]
 pub type Float = f32;
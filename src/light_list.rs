@@ -0,0 +1,180 @@
+use std::fmt::Debug;
+
+use crate::{
+    aabb::AABB,
+    color::Color,
+    hittable::{HitRecord, Hittable, SurfaceSample},
+    interval::Interval,
+    onb::ONB,
+    pdf::{CosinePDF, PDF},
+    ray::Ray,
+    sampler::{random, with_primary_samples, PrimarySampleSpace},
+    Float, Vec3, PI,
+};
+
+// Surface samples used to estimate the power of an emitter.
+const POWER_SAMPLES: usize = 16;
+
+// Seed of the samples behind power estimates, so every run picks lights with the same probabilities.
+const POWER_SEED: u64 = 0x5eed;
+
+// Emitted power estimated from the luminance leaving random points of the surface, on both sides.
+pub fn estimate_power(light: &dyn Hittable) -> Option<Float> {
+    let samples = PrimarySampleSpace::new(POWER_SEED, 0.0, 1.0);
+    let (power, _) = with_primary_samples(samples, || {
+        let mut total = 0.0;
+        for _ in 0..POWER_SAMPLES {
+            let sample = light.sample_surface()?;
+            if sample.pdf <= 0.0 {
+                return None;
+            }
+            // Cosine-distributed directions weigh emitters that are not uniform by their projection.
+            let front = CosinePDF::new(ONB::new(&sample.normal)).generate()?;
+            let back = CosinePDF::new(ONB::new(&-sample.normal)).generate()?;
+            let radiance = emission_towards(light, sample.p, front).luminance()
+                + emission_towards(light, sample.p, back).luminance();
+            total += PI * radiance / sample.pdf;
+        }
+        Some(total / POWER_SAMPLES as Float)
+    });
+    power
+}
+
+// Radiance leaving the light surface at `p` towards `direction`.
+pub fn emission_towards(lights: &dyn Hittable, p: Vec3, direction: Vec3) -> Color {
+    let direction = direction.normalize();
+    let offset = 1.0e-3 * (1.0 + p.abs().max_element());
+    let r = Ray::new(p + direction * offset, -direction);
+    lights
+        .hit(&r, Interval::new(0.0, 2.0 * offset))
+        .map_or(Color::new(0.0, 0.0, 0.0), |rec| {
+            rec.material.emit_color(&r, &rec)
+        })
+}
+
+// Powers of `lights`, with emitters that cannot be sampled given the mean of the others.
+pub fn estimate_powers(lights: &[&dyn Hittable]) -> Vec<Float> {
+    let powers: Vec<Option<Float>> = lights.iter().map(|light| estimate_power(*light)).collect();
+    let known: Vec<Float> = powers.iter().flatten().copied().collect();
+    let fallback = if known.is_empty() {
+        1.0
+    } else {
+        known.iter().sum::<Float>() / known.len() as Float
+    };
+    powers
+        .into_iter()
+        .map(|power| power.unwrap_or(fallback))
+        .collect()
+}
+
+// Emitters picked with probability proportional to their emitted power.
+pub struct LightList<'a> {
+    lights: Vec<&'a dyn Hittable>,
+    probabilities: Vec<Float>,
+    cdf: Vec<Float>,
+    aabb: AABB,
+    name: String,
+}
+
+impl<'a> LightList<'a> {
+    pub fn new(lights: Vec<&'a dyn Hittable>) -> Self {
+        let powers = estimate_powers(&lights);
+        let total: Float = powers.iter().sum();
+        let probabilities: Vec<Float> = if total > 0.0 {
+            powers.iter().map(|power| power / total).collect()
+        } else {
+            vec![1.0 / lights.len() as Float; lights.len()]
+        };
+        let cdf = probabilities
+            .iter()
+            .scan(0.0, |acc, p| {
+                *acc += p;
+                Some(*acc)
+            })
+            .collect();
+        let aabb = lights.iter().fold(AABB::default(), |aabb, light| {
+            aabb.to_contain(&light.bounding_box())
+        });
+
+        Self {
+            lights,
+            probabilities,
+            cdf,
+            aabb,
+            name: "LightList".into(),
+        }
+    }
+
+    fn pick(&self) -> Option<(&'a dyn Hittable, Float)> {
+        let u = random();
+        let index = self
+            .cdf
+            .partition_point(|&c| c <= u)
+            .min(self.lights.len().checked_sub(1)?);
+        Some((self.lights[index], self.probabilities[index]))
+    }
+}
+
+impl<'a> Hittable for LightList<'a> {
+    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
+        let mut rec = None;
+        let mut closest_so_far = ray_t.max;
+        for light in &self.lights {
+            if let Some(hit_record) = light.hit(r, Interval::new(ray_t.min, closest_so_far)) {
+                closest_so_far = hit_record.t;
+                rec = Some(hit_record);
+            }
+        }
+        rec
+    }
+
+    fn bounding_box(&self) -> AABB {
+        self.aabb
+    }
+
+    fn get_name(&self) -> &String {
+        &self.name
+    }
+
+    fn pdf_value(&self, origin: &Vec3, direction: &Vec3) -> Float {
+        self.lights
+            .iter()
+            .zip(&self.probabilities)
+            .map(|(light, probability)| probability * light.pdf_value(origin, direction))
+            .sum()
+    }
+
+    fn random_vector_to_surface(&self, origin: &Vec3) -> Vec3 {
+        self.pick()
+            .map_or(Vec3::X, |(light, _)| light.random_vector_to_surface(origin))
+    }
+
+    fn sample_surface(&self) -> Option<SurfaceSample> {
+        let (light, probability) = self.pick()?;
+        light.sample_surface().map(|sample| SurfaceSample {
+            pdf: sample.pdf * probability,
+            ..sample
+        })
+    }
+
+    fn surface_pdf_value(&self, origin: &Vec3, direction: &Vec3) -> Float {
+        self.lights
+            .iter()
+            .zip(&self.probabilities)
+            .map(|(light, probability)| probability * light.surface_pdf_value(origin, direction))
+            .sum()
+    }
+    fn is_empty(&self) -> bool {
+        self.lights.is_empty()
+    }
+}
+
+impl<'a> Debug for LightList<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.name)?;
+        for light in &self.lights {
+            f.write_fmt(format_args!(" {:?}\n", light))?;
+        }
+        Ok(())
+    }
+}
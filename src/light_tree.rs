@@ -0,0 +1,433 @@
+use std::fmt::Debug;
+
+use crate::{
+    aabb::AABB,
+    hittable::{HitRecord, Hittable, NormalCone},
+    interval::Interval,
+    light_list::estimate_powers,
+    ray::Ray,
+    sampler::random,
+    Float, Mat3, Vec3, PI,
+};
+
+const SPLIT_BUCKETS: usize = 12;
+
+fn safe_sqrt(x: Float) -> Float {
+    x.max(0.0).sqrt()
+}
+
+// cos(max(0, a - b)) from the sines and cosines of a and b.
+fn cos_sub_clamped(sin_a: Float, cos_a: Float, sin_b: Float, cos_b: Float) -> Float {
+    if cos_a > cos_b {
+        1.0
+    } else {
+        cos_a * cos_b + sin_a * sin_b
+    }
+}
+
+fn sin_sub_clamped(sin_a: Float, cos_a: Float, sin_b: Float, cos_b: Float) -> Float {
+    if cos_a > cos_b {
+        0.0
+    } else {
+        sin_a * cos_b - cos_a * sin_b
+    }
+}
+
+fn union_cones(a: NormalCone, b: NormalCone) -> NormalCone {
+    let theta_a = a.cos_theta.clamp(-1.0, 1.0).acos();
+    let theta_b = b.cos_theta.clamp(-1.0, 1.0).acos();
+    let theta_d = a.axis.angle_between(b.axis);
+    if (theta_d + theta_b).min(PI) <= theta_a {
+        return a;
+    }
+    if (theta_d + theta_a).min(PI) <= theta_b {
+        return b;
+    }
+
+    let theta_o = (theta_a + theta_d + theta_b) / 2.0;
+    let rotation_axis = a.axis.cross(b.axis);
+    if theta_o >= PI || rotation_axis.length_squared() == 0.0 {
+        return NormalCone {
+            axis: a.axis,
+            cos_theta: -1.0,
+        };
+    }
+    let rotation = Mat3::from_axis_angle(rotation_axis.normalize(), theta_o - theta_a);
+    NormalCone {
+        axis: rotation * a.axis,
+        cos_theta: theta_o.cos(),
+    }
+}
+
+// A cluster of emitters summarized by where they are, which way they face and how much they emit.
+#[derive(Clone, Copy)]
+struct LightBounds {
+    aabb: AABB,
+    power: Float,
+    cone: NormalCone,
+    // Cosine of how far past the normal cone light is emitted; area emitters reach the horizon.
+    cos_theta_e: Float,
+}
+
+impl LightBounds {
+    fn union(&self, other: &Self) -> Self {
+        Self {
+            aabb: self.aabb.to_contain(&other.aabb),
+            power: self.power + other.power,
+            cone: union_cones(self.cone, other.cone),
+            cos_theta_e: self.cos_theta_e.min(other.cos_theta_e),
+        }
+    }
+
+    fn centroid(&self) -> Vec3 {
+        (self.aabb.min() + self.aabb.max()) / 2.0
+    }
+
+    // Conservative estimate of the light the cluster can send towards `p`.
+    fn importance(&self, p: Vec3) -> Float {
+        let center = self.centroid();
+        let offset = p - center;
+        let distance_squared = offset
+            .length_squared()
+            .max((self.aabb.max() - self.aabb.min()).length() / 2.0);
+
+        // Emitters are two-sided, so only the angle to the nearer side of the cone matters.
+        let cos_theta_w = self.cone.axis.dot(offset.normalize_or_zero()).abs();
+        let sin_theta_w = safe_sqrt(1.0 - cos_theta_w * cos_theta_w);
+
+        let radius_squared = (self.aabb.max() - center).length_squared();
+        let cos_theta_b = if offset.length_squared() < radius_squared {
+            -1.0
+        } else {
+            safe_sqrt(1.0 - radius_squared / offset.length_squared())
+        };
+        let sin_theta_b = safe_sqrt(1.0 - cos_theta_b * cos_theta_b);
+
+        let cos_theta_o = self.cone.cos_theta;
+        let sin_theta_o = safe_sqrt(1.0 - cos_theta_o * cos_theta_o);
+        let cos_theta_x = cos_sub_clamped(sin_theta_w, cos_theta_w, sin_theta_o, cos_theta_o);
+        let sin_theta_x = sin_sub_clamped(sin_theta_w, cos_theta_w, sin_theta_o, cos_theta_o);
+        let cos_theta_p = cos_sub_clamped(sin_theta_x, cos_theta_x, sin_theta_b, cos_theta_b);
+        if cos_theta_p <= self.cos_theta_e {
+            return 0.0;
+        }
+
+        self.power * cos_theta_p / distance_squared
+    }
+
+    // Surface area orientation heuristic cost of the cluster.
+    fn cost(&self) -> Float {
+        let theta_o = self.cone.cos_theta.clamp(-1.0, 1.0).acos();
+        let theta_e = self.cos_theta_e.clamp(-1.0, 1.0).acos();
+        let theta_w = (theta_o + theta_e).min(PI);
+        let (sin_theta_o, cos_theta_o) = theta_o.sin_cos();
+        let solid_angle = 2.0 * PI * (1.0 - cos_theta_o)
+            + PI / 2.0
+                * (2.0 * theta_w * sin_theta_o
+                    - (theta_o - 2.0 * theta_w).cos()
+                    - 2.0 * theta_o * sin_theta_o
+                    + cos_theta_o);
+        let extent = self.aabb.max() - self.aabb.min();
+        let area = 2.0 * (extent.x * extent.y + extent.y * extent.z + extent.z * extent.x);
+        self.power * solid_angle * area.max(Float::EPSILON)
+    }
+}
+
+enum LightNode<'a> {
+    Interior {
+        bounds: LightBounds,
+        children: Box<[LightNode<'a>; 2]>,
+    },
+    Leaf {
+        bounds: LightBounds,
+        light: &'a dyn Hittable,
+    },
+}
+
+impl<'a> LightNode<'a> {
+    fn bounds(&self) -> &LightBounds {
+        match self {
+            Self::Interior { bounds, .. } | Self::Leaf { bounds, .. } => bounds,
+        }
+    }
+
+    fn build(mut lights: Vec<(&'a dyn Hittable, LightBounds)>) -> Self {
+        if lights.len() == 1 {
+            let (light, bounds) = lights[0];
+            return Self::Leaf { bounds, light };
+        }
+
+        let split = Self::split(&mut lights);
+        let right = lights.split_off(split);
+        let left = Self::build(lights);
+        let right = Self::build(right);
+        Self::Interior {
+            bounds: left.bounds().union(right.bounds()),
+            children: Box::new([left, right]),
+        }
+    }
+
+    // Sorts `lights` along the best axis and returns the index of the cheapest bucket boundary.
+    fn split(lights: &mut [(&'a dyn Hittable, LightBounds)]) -> usize {
+        let centroids = lights.iter().fold(AABB::default(), |aabb, (_, bounds)| {
+            aabb.to_contain(&AABB::from_extrema(bounds.centroid(), bounds.centroid()))
+        });
+        let extent = centroids.max() - centroids.min();
+        let mut best: Option<(Float, usize, usize)> = None;
+
+        for axis in 0..3 {
+            if extent[axis] <= 0.0 {
+                continue;
+            }
+            let bucket_of = |bounds: &LightBounds| {
+                let offset = (bounds.centroid()[axis] - centroids.min()[axis]) / extent[axis];
+                ((offset * SPLIT_BUCKETS as Float) as usize).min(SPLIT_BUCKETS - 1)
+            };
+            let mut buckets: [Option<LightBounds>; SPLIT_BUCKETS] = [None; SPLIT_BUCKETS];
+            for (_, bounds) in lights.iter() {
+                let bucket = &mut buckets[bucket_of(bounds)];
+                *bucket = Some(bucket.map_or(*bounds, |b| b.union(bounds)));
+            }
+
+            let regularization = extent.max_element() / extent[axis];
+            for boundary in 1..SPLIT_BUCKETS {
+                let merge = |range: &[Option<LightBounds>]| {
+                    range
+                        .iter()
+                        .flatten()
+                        .fold(None, |acc: Option<LightBounds>, b| {
+                            Some(acc.map_or(*b, |acc| acc.union(b)))
+                        })
+                };
+                let (Some(below), Some(above)) =
+                    (merge(&buckets[..boundary]), merge(&buckets[boundary..]))
+                else {
+                    continue;
+                };
+                let cost = regularization * (below.cost() + above.cost());
+                let better = match best {
+                    Some((best_cost, _, _)) => cost < best_cost,
+                    None => true,
+                };
+                if better {
+                    best = Some((cost, axis, boundary));
+                }
+            }
+        }
+
+        let Some((_, axis, boundary)) = best else {
+            return lights.len() / 2;
+        };
+        lights.sort_by(|(_, a), (_, b)| a.centroid()[axis].total_cmp(&b.centroid()[axis]));
+        let threshold =
+            centroids.min()[axis] + extent[axis] * boundary as Float / SPLIT_BUCKETS as Float;
+        lights
+            .iter()
+            .position(|(_, bounds)| bounds.centroid()[axis] >= threshold)
+            .unwrap_or(lights.len() / 2)
+            .clamp(1, lights.len() - 1)
+    }
+
+    // Probabilities of descending into each child when shading at `p`.
+    fn child_probabilities(children: &[LightNode; 2], p: Vec3) -> [Float; 2] {
+        let left = children[0].bounds().importance(p);
+        let right = children[1].bounds().importance(p);
+        if left + right <= 0.0 {
+            [0.5, 0.5]
+        } else {
+            [left / (left + right), right / (left + right)]
+        }
+    }
+
+    fn pdf_value(&self, ray: &Ray) -> Float {
+        if !self
+            .bounds()
+            .aabb
+            .hit(ray, Interval::new(0.0001, Float::MAX))
+        {
+            return 0.0;
+        }
+        match self {
+            Self::Leaf { light, .. } => light.pdf_value(&ray.origin, &ray.direction),
+            Self::Interior { children, .. } => {
+                let probabilities = Self::child_probabilities(children, ray.origin);
+                children
+                    .iter()
+                    .zip(probabilities)
+                    .filter(|(_, probability)| *probability > 0.0)
+                    .map(|(child, probability)| probability * child.pdf_value(ray))
+                    .sum()
+            }
+        }
+    }
+
+    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
+        if !self.bounds().aabb.hit(r, ray_t) {
+            return None;
+        }
+        match self {
+            Self::Leaf { light, .. } => light.hit(r, ray_t),
+            Self::Interior { children, .. } => {
+                let left = children[0].hit(r, ray_t);
+                let closest = left.as_ref().map_or(ray_t.max, |rec| rec.t);
+                children[1]
+                    .hit(r, Interval::new(ray_t.min, closest))
+                    .or(left)
+            }
+        }
+    }
+}
+
+// Emitters clustered into a binary tree that is traversed stochastically to pick a light for a
+// shading point, with probability proportional to its estimated contribution there.
+pub struct LightTree<'a> {
+    root: Option<LightNode<'a>>,
+    aabb: AABB,
+    name: String,
+}
+
+impl<'a> LightTree<'a> {
+    pub fn new(lights: Vec<&'a dyn Hittable>) -> Self {
+        let powers = estimate_powers(&lights);
+        let mut aabb = AABB::default();
+        let bounded: Vec<(&'a dyn Hittable, LightBounds)> = lights
+            .into_iter()
+            .zip(powers)
+            .map(|(light, power)| {
+                aabb = aabb.to_contain(&light.bounding_box());
+                let bounds = LightBounds {
+                    aabb: Self::padded(light.bounding_box()),
+                    power,
+                    cone: light.normal_cone(),
+                    cos_theta_e: 0.0,
+                };
+                (light, bounds)
+            })
+            .collect();
+
+        Self {
+            root: if bounded.is_empty() {
+                None
+            } else {
+                Some(LightNode::build(bounded))
+            },
+            aabb,
+            name: "LightTree".into(),
+        }
+    }
+
+    // Flat emitters have boxes without thickness, which rays never enter.
+    fn padded(aabb: AABB) -> AABB {
+        let delta = Vec3::splat(1.0e-4 * (1.0 + (aabb.max() - aabb.min()).max_element()));
+        AABB::from_extrema(aabb.min() - delta, aabb.max() + delta)
+    }
+}
+
+impl<'a> Hittable for LightTree<'a> {
+    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
+        self.root.as_ref()?.hit(r, ray_t)
+    }
+
+    fn bounding_box(&self) -> AABB {
+        self.aabb
+    }
+
+    fn get_name(&self) -> &String {
+        &self.name
+    }
+
+    fn pdf_value(&self, origin: &Vec3, direction: &Vec3) -> Float {
+        self.root
+            .as_ref()
+            .map_or(0.0, |root| root.pdf_value(&Ray::new(*origin, *direction)))
+    }
+
+    fn random_vector_to_surface(&self, origin: &Vec3) -> Vec3 {
+        let Some(mut node) = self.root.as_ref() else {
+            return Vec3::X;
+        };
+        loop {
+            match node {
+                LightNode::Leaf { light, .. } => return light.random_vector_to_surface(origin),
+                LightNode::Interior { children, .. } => {
+                    let [left, _] = LightNode::child_probabilities(children, *origin);
+                    node = if random() < left {
+                        &children[0]
+                    } else {
+                        &children[1]
+                    };
+                }
+            }
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.root.is_none()
+    }
+}
+
+impl<'a> Debug for LightTree<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.name)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        color::Color,
+        materials::diffuse_light::DiffuseLightMaterial,
+        pdf::tests::{assert_samples_match, integrate},
+        quad::Quad,
+        texture::ColorTexture2D,
+    };
+
+    #[test]
+    fn sampling_matches_the_density() {
+        let bright = ColorTexture2D {
+            color: Color::new(4.0, 4.0, 4.0),
+        };
+        let dim = ColorTexture2D {
+            color: Color::new(1.0, 1.0, 1.0),
+        };
+        let (bright, dim) = (
+            DiffuseLightMaterial::new(&bright),
+            DiffuseLightMaterial::new(&dim),
+        );
+        let quads = [
+            Quad::new(
+                Vec3::new(-1.0, -1.0, 2.0),
+                Vec3::new(2.0, 0.0, 0.0),
+                Vec3::new(0.0, 2.0, 0.0),
+                &bright,
+            ),
+            Quad::new(
+                Vec3::new(3.0, -1.0, -1.0),
+                Vec3::new(0.0, 0.0, 2.0),
+                Vec3::new(0.0, 1.0, 1.0),
+                &dim,
+            ),
+            Quad::new(
+                Vec3::new(-1.0, -2.5, -1.0),
+                Vec3::new(0.0, 0.0, 1.5),
+                Vec3::new(1.5, 0.0, 0.0),
+                &dim,
+            ),
+        ];
+        let tree = LightTree::new(quads.iter().map(|quad| quad as &dyn Hittable).collect());
+        let origin = Vec3::new(0.2, 0.1, -0.3);
+
+        let total = integrate(|direction| tree.pdf_value(&origin, &direction));
+        assert!(
+            (total - 1.0).abs() < 1.0e-2,
+            "The density integrates to {}",
+            total
+        );
+        assert_samples_match(
+            || Some(tree.random_vector_to_surface(&origin)),
+            |direction| tree.pdf_value(&origin, &direction),
+        );
+    }
+}
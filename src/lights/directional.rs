@@ -0,0 +1,74 @@
+use crate::{color::Color, onb::ONB, ray::Ray, sampler::random, Float, Vec3, PI};
+
+use super::light::{Light, LightSample};
+
+// A light infinitely far away, such as the sun. With an angular diameter it becomes a small
+// disc of constant radiance that casts soft shadows and can be seen by rays leaving the scene.
+pub struct DirectionalLight {
+    direction: Vec3,
+    irradiance: Color,
+    cos_radius: Float,
+}
+
+impl DirectionalLight {
+    // `direction` points towards the light; `irradiance` is measured facing it.
+    pub fn new(direction: Vec3, irradiance: Color, angular_diameter: Float) -> Self {
+        Self {
+            direction: direction.normalize(),
+            irradiance,
+            cos_radius: (angular_diameter.clamp(0.0, 180.0) / 2.0)
+                .to_radians()
+                .cos(),
+        }
+    }
+
+    fn solid_angle(&self) -> Float {
+        2.0 * PI * (1.0 - self.cos_radius)
+    }
+
+    fn radiance(&self) -> Color {
+        self.irradiance / self.solid_angle()
+    }
+}
+
+impl Light for DirectionalLight {
+    fn sample_incident(&self, _p: &Vec3) -> Option<LightSample> {
+        if self.is_delta() {
+            return Some(LightSample {
+                direction: self.direction,
+                distance: Float::MAX,
+                radiance: self.irradiance,
+                pdf: 1.0,
+            });
+        }
+
+        let cos_theta = 1.0 - random() * (1.0 - self.cos_radius);
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        let phi = 2.0 * PI * random();
+        let local = Vec3::new(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta);
+        Some(LightSample {
+            direction: ONB::new(&self.direction).transform(&local).normalize(),
+            distance: Float::MAX,
+            radiance: self.radiance(),
+            pdf: 1.0 / self.solid_angle(),
+        })
+    }
+
+    fn is_delta(&self) -> bool {
+        self.cos_radius >= 1.0
+    }
+
+    fn pdf_value(&self, _origin: &Vec3, direction: &Vec3) -> Float {
+        if self.is_delta() || direction.normalize().dot(self.direction) < self.cos_radius {
+            return 0.0;
+        }
+        1.0 / self.solid_angle()
+    }
+
+    fn emit_color(&self, r: &Ray) -> Color {
+        if self.is_delta() || r.direction.normalize().dot(self.direction) < self.cos_radius {
+            return Color::new(0.0, 0.0, 0.0);
+        }
+        self.radiance()
+    }
+}
@@ -0,0 +1,138 @@
+use image::Rgb32FImage;
+
+use crate::{
+    color::Color, distribution::Distribution2D, ray::Ray, sampler::random, Float, Mat3, Vec2, Vec3,
+    PI,
+};
+
+use super::light::{Light, LightSample};
+
+// Light arriving from every direction, read from an equirectangular (latitude-longitude) image.
+// Directions are importance sampled in proportion to the luminance of the pixels they land in.
+pub struct EnvironmentLight {
+    pixels: Vec<Color>,
+    width: usize,
+    height: usize,
+    distribution: Distribution2D,
+    rotation: Mat3,
+    inverse_rotation: Mat3,
+    intensity: Float,
+    visible_to_camera: bool,
+}
+
+impl EnvironmentLight {
+    // Loads an `.hdr` or `.exr` image. `rotation` turns the image around the up axis, in degrees.
+    pub fn new(path: String, intensity: Float, rotation: Float, visible_to_camera: bool) -> Self {
+        let img = image::open(&path)
+            .unwrap_or_else(|e| panic!("{path} is not a readable environment map: {e}"))
+            .into_rgb32f();
+        Self::from_image(&img, intensity, rotation, visible_to_camera)
+    }
+
+    pub fn from_image(
+        img: &Rgb32FImage,
+        intensity: Float,
+        rotation: Float,
+        visible_to_camera: bool,
+    ) -> Self {
+        let (width, height) = (img.width() as usize, img.height() as usize);
+        let pixels: Vec<Color> = img
+            .pixels()
+            .map(|p| Color::new(p.0[0] as Float, p.0[1] as Float, p.0[2] as Float))
+            .collect();
+        // Rows near the poles cover less solid angle than those at the equator.
+        let weights: Vec<Float> = pixels
+            .iter()
+            .enumerate()
+            .map(|(i, color)| {
+                let theta = PI * ((i / width) as Float + 0.5) / height as Float;
+                color.luminance() * theta.sin()
+            })
+            .collect();
+        let rotation = Mat3::from_rotation_y(rotation.to_radians());
+
+        Self {
+            distribution: Distribution2D::new(&weights, width, height),
+            pixels,
+            width,
+            height,
+            rotation,
+            inverse_rotation: rotation.transpose(),
+            intensity,
+            visible_to_camera,
+        }
+    }
+
+    fn to_uv(&self, direction: &Vec3) -> Vec2 {
+        equirect_uv(self.inverse_rotation * *direction)
+    }
+
+    fn to_direction(&self, uv: Vec2) -> Vec3 {
+        self.rotation * equirect_direction(uv)
+    }
+
+    fn lookup(&self, uv: Vec2) -> Color {
+        let x = ((uv.x * self.width as Float) as usize).min(self.width - 1);
+        let y = ((uv.y * self.height as Float) as usize).min(self.height - 1);
+        self.pixels[y * self.width + x] * self.intensity
+    }
+}
+
+impl Light for EnvironmentLight {
+    fn sample_incident(&self, _p: &Vec3) -> Option<LightSample> {
+        let (uv, _) = self.distribution.sample(Vec2::new(random(), random()));
+        let pdf = equirect_pdf(&self.distribution, uv);
+        if pdf <= 0.0 {
+            return None;
+        }
+        Some(LightSample {
+            direction: self.to_direction(uv),
+            distance: Float::MAX,
+            radiance: self.lookup(uv),
+            pdf,
+        })
+    }
+
+    fn is_delta(&self) -> bool {
+        false
+    }
+
+    fn pdf_value(&self, _origin: &Vec3, direction: &Vec3) -> Float {
+        equirect_pdf(&self.distribution, self.to_uv(direction))
+    }
+
+    fn emit_color(&self, r: &Ray) -> Color {
+        self.lookup(self.to_uv(&r.direction))
+    }
+
+    fn visible_to_camera(&self) -> bool {
+        self.visible_to_camera
+    }
+}
+
+// Latitude-longitude coordinates of a direction, with v = 0 straight up.
+pub(crate) fn equirect_uv(direction: Vec3) -> Vec2 {
+    let d = direction.normalize();
+    let theta = Vec2::new(d.x, d.z).length().atan2(d.y);
+    let phi = d.z.atan2(d.x) + PI;
+    Vec2::new((phi / (2.0 * PI)).fract(), theta / PI)
+}
+
+pub(crate) fn equirect_direction(uv: Vec2) -> Vec3 {
+    let phi = uv.x * 2.0 * PI - PI;
+    let theta = uv.y * PI;
+    Vec3::new(
+        theta.sin() * phi.cos(),
+        theta.cos(),
+        theta.sin() * phi.sin(),
+    )
+}
+
+// Converts a density over the latitude-longitude square to one over solid angle.
+pub(crate) fn equirect_pdf(distribution: &Distribution2D, uv: Vec2) -> Float {
+    let sin_theta = (uv.y * PI).sin();
+    if sin_theta <= 0.0 {
+        return 0.0;
+    }
+    distribution.pdf(uv) / (2.0 * PI * PI * sin_theta)
+}
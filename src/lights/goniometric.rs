@@ -0,0 +1,51 @@
+use crate::{color::Color, onb::ONB, Vec3};
+
+use super::{
+    ies::IesProfile,
+    light::{Light, LightSample},
+};
+
+// A point light whose intensity varies with direction as measured for a real luminaire.
+pub struct GoniometricLight {
+    position: Vec3,
+    frame: ONB,
+    profile: IesProfile,
+    color: Color,
+}
+
+impl GoniometricLight {
+    // `nadir` is the direction the profile's zero vertical angle points to, usually straight down.
+    // Candela values are multiplied by `color`.
+    pub fn new(position: Vec3, nadir: Vec3, profile: IesProfile, color: Color) -> Self {
+        Self {
+            position,
+            frame: ONB::new(&nadir),
+            profile,
+            color,
+        }
+    }
+}
+
+impl Light for GoniometricLight {
+    fn sample_incident(&self, p: &Vec3) -> Option<LightSample> {
+        let offset = self.position - *p;
+        let distance_squared = offset.length_squared();
+        if distance_squared <= 0.0 {
+            return None;
+        }
+        let direction = offset.normalize();
+        let local = self.frame.to_local(&-direction);
+        let vertical = local.z.clamp(-1.0, 1.0).acos().to_degrees();
+        let horizontal = local.y.atan2(local.x).to_degrees();
+        let candela = self.profile.candela(vertical, horizontal);
+        if candela <= 0.0 {
+            return None;
+        }
+        Some(LightSample {
+            direction,
+            distance: distance_squared.sqrt(),
+            radiance: self.color * candela / distance_squared,
+            pdf: 1.0,
+        })
+    }
+}
@@ -0,0 +1,196 @@
+use crate::Float;
+
+// Candela values of a luminaire in the IES LM-63 photometric format, over vertical angles from
+// the nadir and horizontal angles around it, both in degrees.
+pub struct IesProfile {
+    vertical_angles: Vec<Float>,
+    horizontal_angles: Vec<Float>,
+    // One row of vertical samples per horizontal angle.
+    candela: Vec<Vec<Float>>,
+}
+
+impl IesProfile {
+    pub fn from_file(path: String) -> Self {
+        let text = std::fs::read_to_string(&path).unwrap();
+        Self::parse(&text).unwrap_or_else(|| panic!("{path} is not a valid IES profile"))
+    }
+
+    pub fn parse(text: &str) -> Option<Self> {
+        // Keywords come first; the numeric data starts after the TILT line.
+        let mut lines = text.lines();
+        let tilt = lines.find(|line| line.trim_start().starts_with("TILT="))?;
+        let mut numbers = lines
+            .flat_map(|line| line.split(|c: char| c.is_whitespace() || c == ','))
+            .filter(|token| !token.is_empty())
+            .map(|token| token.parse::<Float>().ok());
+        let mut next = || numbers.next().flatten();
+
+        if tilt.trim() == "TILT=INCLUDE" {
+            let _lamp_to_luminaire_geometry = next()?;
+            let pairs = next()? as usize;
+            for _ in 0..2 * pairs {
+                next()?;
+            }
+        }
+
+        let _lamps = next()?;
+        let _lumens_per_lamp = next()?;
+        let multiplier = next()?;
+        let vertical_count = next()? as usize;
+        let horizontal_count = next()? as usize;
+        let _photometric_type = next()?;
+        let _units = next()?;
+        let (_width, _length, _height) = (next()?, next()?, next()?);
+        let ballast_factor = next()?;
+        let _future_use = next()?;
+        let _input_watts = next()?;
+
+        let vertical_angles = (0..vertical_count)
+            .map(|_| next())
+            .collect::<Option<Vec<Float>>>()?;
+        let horizontal_angles = (0..horizontal_count)
+            .map(|_| next())
+            .collect::<Option<Vec<Float>>>()?;
+        let candela = (0..horizontal_count)
+            .map(|_| {
+                (0..vertical_count)
+                    .map(|_| next().map(|value| value * multiplier * ballast_factor))
+                    .collect::<Option<Vec<Float>>>()
+            })
+            .collect::<Option<Vec<Vec<Float>>>>()?;
+
+        if vertical_angles.is_empty() || horizontal_angles.is_empty() {
+            return None;
+        }
+        Some(Self {
+            vertical_angles,
+            horizontal_angles,
+            candela,
+        })
+    }
+
+    pub fn max_candela(&self) -> Float {
+        self.candela
+            .iter()
+            .flatten()
+            .fold(0.0, |max: Float, value| max.max(*value))
+    }
+
+    // Luminous intensity towards `vertical` degrees from the nadir and `horizontal` degrees around it.
+    pub fn candela(&self, vertical: Float, horizontal: Float) -> Float {
+        let horizontal = self.fold_horizontal(horizontal.rem_euclid(360.0));
+        let (h0, h1, th) = bracket(&self.horizontal_angles, horizontal);
+        let Some((v0, v1, tv)) = bracket_within(&self.vertical_angles, vertical) else {
+            return 0.0;
+        };
+        let along = |h: usize| self.candela[h][v0] * (1.0 - tv) + self.candela[h][v1] * tv;
+        along(h0) * (1.0 - th) + along(h1) * th
+    }
+
+    // Profiles only cover the horizontal angles that their symmetry does not repeat.
+    fn fold_horizontal(&self, horizontal: Float) -> Float {
+        let last = self.horizontal_angles[self.horizontal_angles.len() - 1];
+        if last <= 0.0 {
+            0.0
+        } else if last <= 90.0 {
+            let h = horizontal % 180.0;
+            if h > 90.0 {
+                180.0 - h
+            } else {
+                h
+            }
+        } else if last <= 180.0 && horizontal > 180.0 {
+            360.0 - horizontal
+        } else {
+            horizontal
+        }
+    }
+}
+
+// The samples around `x` and how far it lies between them, clamping outside the range.
+fn bracket(angles: &[Float], x: Float) -> (usize, usize, Float) {
+    let upper = angles.partition_point(|&a| a <= x);
+    if upper == 0 {
+        return (0, 0, 0.0);
+    }
+    if upper == angles.len() {
+        return (upper - 1, upper - 1, 0.0);
+    }
+    let (a0, a1) = (angles[upper - 1], angles[upper]);
+    (upper - 1, upper, (x - a0) / (a1 - a0))
+}
+
+// Like `bracket`, but no light leaves outside the measured range.
+fn bracket_within(angles: &[Float], x: Float) -> Option<(usize, usize, Float)> {
+    if x < angles[0] || x > angles[angles.len() - 1] {
+        return None;
+    }
+    Some(bracket(angles, x))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const QUADRANT: &str = "IESNA:LM-63-2002
+[TEST] quadrant
+TILT=NONE
+1 1000 2 3 3 1 2 0.1 0.1 0
+1.0 1.0 50
+0 45 90
+0 45 90
+100 80 10
+60 50, 5
+20 10 0
+";
+
+    fn close(a: Float, b: Float) -> bool {
+        (a - b).abs() < 1.0e-3
+    }
+
+    #[test]
+    fn parses_and_scales_candela() {
+        let profile = IesProfile::parse(QUADRANT).unwrap();
+        assert!(close(profile.candela(0.0, 0.0), 200.0));
+        assert!(close(profile.candela(90.0, 45.0), 10.0));
+        assert!(close(profile.max_candela(), 200.0));
+    }
+
+    #[test]
+    fn interpolates_between_angles() {
+        let profile = IesProfile::parse(QUADRANT).unwrap();
+        assert!(close(profile.candela(22.5, 0.0), 180.0));
+        assert!(close(profile.candela(0.0, 22.5), 160.0));
+        assert!(close(profile.candela(22.5, 22.5), 145.0));
+    }
+
+    #[test]
+    fn folds_symmetric_quadrants() {
+        let profile = IesProfile::parse(QUADRANT).unwrap();
+        for vertical in [0.0, 30.0, 60.0] {
+            let candela = profile.candela(vertical, 30.0);
+            for horizontal in [150.0, 210.0, 330.0, -30.0] {
+                assert!(close(profile.candela(vertical, horizontal), candela));
+            }
+        }
+    }
+
+    #[test]
+    fn emits_nothing_outside_the_vertical_range() {
+        let profile = IesProfile::parse(QUADRANT).unwrap();
+        assert_eq!(profile.candela(120.0, 0.0), 0.0);
+    }
+
+    #[test]
+    fn skips_included_tilt_data() {
+        let text = QUADRANT.replace("TILT=NONE", "TILT=INCLUDE\n1\n2\n0 90\n1 1");
+        let profile = IesProfile::parse(&text).unwrap();
+        assert!(close(profile.candela(0.0, 0.0), 200.0));
+    }
+
+    #[test]
+    fn rejects_truncated_data() {
+        assert!(IesProfile::parse(&QUADRANT[..QUADRANT.len() - 8]).is_none());
+        assert!(IesProfile::parse("IESNA:LM-63-2002\n1 2 3").is_none());
+    }
+}
@@ -0,0 +1,28 @@
+use crate::{color::Color, ray::Ray, Float, Vec3};
+
+pub struct LightSample {
+    pub direction: Vec3,
+    pub distance: Float,
+    pub radiance: Color,
+    pub pdf: Float,
+}
+
+// Lights that are not part of the geometry, so they can only be reached by sampling them explicitly.
+pub trait Light: Send + Sync {
+    // Picks a unit direction from `p` towards the light; delta lights report a pdf of one.
+    fn sample_incident(&self, p: &Vec3) -> Option<LightSample>;
+    fn is_delta(&self) -> bool {
+        true
+    }
+    fn pdf_value(&self, _origin: &Vec3, _direction: &Vec3) -> Float {
+        0.0
+    }
+    // Radiance carried by a ray that leaves the scene.
+    fn emit_color(&self, _r: &Ray) -> Color {
+        Color::new(0.0, 0.0, 0.0)
+    }
+    // Lights hidden from the camera still light the scene and show in reflections.
+    fn visible_to_camera(&self) -> bool {
+        true
+    }
+}
@@ -0,0 +1,8 @@
+pub mod directional;
+pub mod environment;
+pub mod goniometric;
+pub mod ies;
+pub mod light;
+pub mod point;
+pub mod sky;
+pub mod spot;
@@ -0,0 +1,33 @@
+use crate::{color::Color, Vec3};
+
+use super::light::{Light, LightSample};
+
+pub struct PointLight {
+    position: Vec3,
+    intensity: Color,
+}
+
+impl PointLight {
+    pub fn new(position: Vec3, intensity: Color) -> Self {
+        Self {
+            position,
+            intensity,
+        }
+    }
+}
+
+impl Light for PointLight {
+    fn sample_incident(&self, p: &Vec3) -> Option<LightSample> {
+        let offset = self.position - *p;
+        let distance_squared = offset.length_squared();
+        if distance_squared <= 0.0 {
+            return None;
+        }
+        Some(LightSample {
+            direction: offset.normalize(),
+            distance: distance_squared.sqrt(),
+            radiance: self.intensity / distance_squared,
+            pdf: 1.0,
+        })
+    }
+}
@@ -0,0 +1,275 @@
+use crate::{
+    color::Color, distribution::Distribution2D, ray::Ray, sampler::random, spectrum::xyz_to_rgb,
+    Float, Vec2, Vec3, PI,
+};
+
+use super::{
+    directional::DirectionalLight,
+    environment::{equirect_direction, equirect_pdf, equirect_uv},
+    light::{Light, LightSample},
+};
+
+// Resolution of the table used to importance sample the sky.
+const TABLE_WIDTH: usize = 128;
+const TABLE_HEIGHT: usize = 64;
+const SUN_ANGULAR_DIAMETER: Float = 0.53;
+// Luminance of the sun outside the atmosphere, in the sky's units of kcd/m^2.
+const SUN_LUMINANCE: Float = 1.6e6;
+// Wavelengths in micrometres standing in for the red, green and blue channels.
+const WAVELENGTHS: [Float; 3] = [0.65, 0.57, 0.475];
+
+// Perez et al. luminance distribution coefficients.
+struct Perez([Float; 5]);
+
+impl Perez {
+    fn new(turbidity: Float, coefficients: [[Float; 2]; 5]) -> Self {
+        Self(coefficients.map(|[t, c]| t * turbidity + c))
+    }
+
+    fn value(&self, cos_theta: Float, gamma: Float) -> Float {
+        let [a, b, c, d, e] = self.0;
+        (1.0 + a * (b / cos_theta.max(1.0e-3)).exp())
+            * (1.0 + c * (d * gamma).exp() + e * gamma.cos() * gamma.cos())
+    }
+}
+
+// The Preetham, Shirley and Smits analytic daylight model, with the sun as a small disc.
+// Radiance is in kcd/m^2 scaled by `intensity`; nothing is emitted below the horizon.
+pub struct SkyLight {
+    sun_direction: Vec3,
+    zenith: [Float; 3],
+    perez: [Perez; 3],
+    intensity: Float,
+    sun: DirectionalLight,
+    sun_probability: Float,
+    distribution: Distribution2D,
+}
+
+impl SkyLight {
+    // `sun_direction` points towards the sun with y up; `turbidity` ranges from 2 (clear) to 10 (hazy).
+    pub fn new(sun_direction: Vec3, turbidity: Float, intensity: Float) -> Self {
+        let sun_direction = sun_direction.normalize();
+        let t = turbidity.clamp(1.7, 10.0);
+        let theta_s = sun_direction.y.clamp(-1.0, 1.0).acos().min(PI / 2.0);
+
+        let chi = (4.0 / 9.0 - t / 120.0) * (PI - 2.0 * theta_s);
+        let zenith_luminance = ((4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192).max(0.0);
+        let chromaticity = |m: [[Float; 4]; 3]| {
+            let thetas = [theta_s.powi(3), theta_s.powi(2), theta_s, 1.0];
+            let ts = [t * t, t, 1.0];
+            (0..3)
+                .map(|i| ts[i] * (0..4).map(|j| m[i][j] * thetas[j]).sum::<Float>())
+                .sum::<Float>()
+        };
+        let zenith_x = chromaticity([
+            [0.00166, -0.00375, 0.00209, 0.0],
+            [-0.02903, 0.06377, -0.03202, 0.00394],
+            [0.11693, -0.21196, 0.06052, 0.25886],
+        ]);
+        let zenith_y = chromaticity([
+            [0.00275, -0.00610, 0.00317, 0.0],
+            [-0.04214, 0.08970, -0.04153, 0.00516],
+            [0.15346, -0.26756, 0.06670, 0.26688],
+        ]);
+        let perez = [
+            Perez::new(
+                t,
+                [
+                    [0.1787, -1.4630],
+                    [-0.3554, 0.4275],
+                    [-0.0227, 5.3251],
+                    [0.1206, -2.5771],
+                    [-0.0670, 0.3703],
+                ],
+            ),
+            Perez::new(
+                t,
+                [
+                    [-0.0193, -0.2592],
+                    [-0.0665, 0.0008],
+                    [-0.0004, 0.2125],
+                    [-0.0641, -0.8989],
+                    [-0.0033, 0.0452],
+                ],
+            ),
+            Perez::new(
+                t,
+                [
+                    [-0.0167, -0.2608],
+                    [-0.0950, 0.0092],
+                    [-0.0079, 0.2102],
+                    [-0.0441, -1.6537],
+                    [-0.0109, 0.0529],
+                ],
+            ),
+        ];
+        // Values at the zenith are scaled so that the Perez function is one there.
+        let zenith_values = [zenith_luminance, zenith_x, zenith_y];
+        let zenith = [0, 1, 2].map(|i| zenith_values[i] / perez[i].value(1.0, theta_s));
+
+        let sun_solid_angle = 2.0 * PI * (1.0 - (SUN_ANGULAR_DIAMETER / 2.0).to_radians().cos());
+        let sun_radiance = sun_transmittance(sun_direction, t) * SUN_LUMINANCE * intensity;
+        let sun = DirectionalLight::new(
+            sun_direction,
+            sun_radiance * sun_solid_angle,
+            SUN_ANGULAR_DIAMETER,
+        );
+
+        let mut sky = Self {
+            sun_direction,
+            zenith,
+            perez,
+            intensity,
+            sun,
+            sun_probability: 0.0,
+            distribution: Distribution2D::new(&[0.0], 1, 1),
+        };
+
+        let weights: Vec<Float> = (0..TABLE_WIDTH * TABLE_HEIGHT)
+            .map(|i| {
+                let uv = Vec2::new(
+                    ((i % TABLE_WIDTH) as Float + 0.5) / TABLE_WIDTH as Float,
+                    ((i / TABLE_WIDTH) as Float + 0.5) / TABLE_HEIGHT as Float,
+                );
+                sky.sky_radiance(equirect_direction(uv)).luminance() * (uv.y * PI).sin()
+            })
+            .collect();
+        let sky_power =
+            weights.iter().sum::<Float>() * 2.0 * PI * PI / (TABLE_WIDTH * TABLE_HEIGHT) as Float;
+        let sun_power = sun_radiance.luminance() * sun_solid_angle;
+        sky.distribution = Distribution2D::new(&weights, TABLE_WIDTH, TABLE_HEIGHT);
+        sky.sun_probability = if sun_power + sky_power > 0.0 {
+            sun_power / (sun_power + sky_power)
+        } else {
+            0.0
+        };
+        sky
+    }
+
+    fn sky_radiance(&self, direction: Vec3) -> Color {
+        let d = direction.normalize();
+        if d.y <= 0.0 {
+            return Color::new(0.0, 0.0, 0.0);
+        }
+        let gamma = d.dot(self.sun_direction).clamp(-1.0, 1.0).acos();
+        let [luminance, x, y] = [0, 1, 2].map(|i| self.zenith[i] * self.perez[i].value(d.y, gamma));
+        xyy_to_rgb(x, y, luminance) * self.intensity
+    }
+
+    fn radiance(&self, r: &Ray) -> Color {
+        self.sky_radiance(r.direction) + self.sun.emit_color(r)
+    }
+}
+
+impl Light for SkyLight {
+    fn sample_incident(&self, p: &Vec3) -> Option<LightSample> {
+        let direction = if random() < self.sun_probability {
+            self.sun.sample_incident(p)?.direction
+        } else {
+            equirect_direction(self.distribution.sample(Vec2::new(random(), random())).0)
+        };
+        let pdf = self.pdf_value(p, &direction);
+        if pdf <= 0.0 {
+            return None;
+        }
+        Some(LightSample {
+            direction,
+            distance: Float::MAX,
+            radiance: self.radiance(&Ray::new(*p, direction)),
+            pdf,
+        })
+    }
+
+    fn is_delta(&self) -> bool {
+        false
+    }
+
+    fn pdf_value(&self, origin: &Vec3, direction: &Vec3) -> Float {
+        self.sun_probability * self.sun.pdf_value(origin, direction)
+            + (1.0 - self.sun_probability)
+                * equirect_pdf(&self.distribution, equirect_uv(*direction))
+    }
+
+    fn emit_color(&self, r: &Ray) -> Color {
+        self.radiance(r)
+    }
+}
+
+fn xyy_to_rgb(x: Float, y: Float, luminance: Float) -> Color {
+    if y <= 0.0 {
+        return Color::new(0.0, 0.0, 0.0);
+    }
+    xyz_to_rgb(Vec3::new(x / y, 1.0, (1.0 - x - y) / y) * luminance)
+}
+
+// Fraction of sunlight that crosses the atmosphere through Rayleigh and aerosol scattering.
+fn sun_transmittance(sun_direction: Vec3, turbidity: Float) -> Color {
+    if sun_direction.y <= 0.0 {
+        return Color::new(0.0, 0.0, 0.0);
+    }
+    let zenith_degrees = sun_direction.y.acos().to_degrees();
+    let air_mass = 1.0 / (sun_direction.y + 0.50572 * (96.07995 - zenith_degrees).powf(-1.6364));
+    let beta = 0.04608 * turbidity - 0.04586;
+    let [r, g, b] = WAVELENGTHS.map(|lambda| {
+        let rayleigh = 0.008735 * lambda.powf(-4.08);
+        let aerosol = beta * lambda.powf(-1.3);
+        (-air_mass * (rayleigh + aerosol)).exp()
+    });
+    Color::new(r, g, b)
+}
+
+// Direction towards the sun, with x pointing east, y up and z north. `latitude` and `longitude`
+// are in degrees, north and east positive; `hours` is the time of day in UTC.
+pub fn sun_direction(
+    latitude: Float,
+    longitude: Float,
+    year: i32,
+    month: u32,
+    day: u32,
+    hours: Float,
+) -> Vec3 {
+    let is_leap = (year % 4 == 0 && year % 100 != 0) || year % 400 == 0;
+    let days_in_month = [
+        31,
+        if is_leap { 29 } else { 28 },
+        31,
+        30,
+        31,
+        30,
+        31,
+        31,
+        30,
+        31,
+        30,
+        31,
+    ];
+    let day_of_year: u32 = days_in_month
+        .iter()
+        .take(month.clamp(1, 12) as usize - 1)
+        .sum::<u32>()
+        + day;
+    let days_in_year = if is_leap { 366.0 } else { 365.0 };
+
+    // NOAA's approximations of the declination and the equation of time, in radians and minutes.
+    let g = 2.0 * PI / days_in_year * (day_of_year as Float - 1.0 + (hours - 12.0) / 24.0);
+    let equation_of_time = 229.18
+        * (0.000075 + 0.001868 * g.cos()
+            - 0.032077 * g.sin()
+            - 0.014615 * (2.0 * g).cos()
+            - 0.040849 * (2.0 * g).sin());
+    let declination = 0.006918 - 0.399912 * g.cos() + 0.070257 * g.sin()
+        - 0.006758 * (2.0 * g).cos()
+        + 0.000907 * (2.0 * g).sin()
+        - 0.002697 * (3.0 * g).cos()
+        + 0.00148 * (3.0 * g).sin();
+    let solar_minutes = hours * 60.0 + equation_of_time + 4.0 * longitude;
+    let hour_angle = (solar_minutes / 4.0 - 180.0).to_radians();
+    let latitude = latitude.to_radians();
+
+    Vec3::new(
+        -declination.cos() * hour_angle.sin(),
+        latitude.sin() * declination.sin() + latitude.cos() * declination.cos() * hour_angle.cos(),
+        latitude.cos() * declination.sin() - latitude.sin() * declination.cos() * hour_angle.cos(),
+    )
+    .normalize()
+}
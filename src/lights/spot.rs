@@ -0,0 +1,63 @@
+use crate::{color::Color, Float, Vec3};
+
+use super::light::{Light, LightSample};
+
+pub struct SpotLight {
+    position: Vec3,
+    direction: Vec3,
+    intensity: Color,
+    cos_cone: Float,
+    cos_falloff_start: Float,
+}
+
+impl SpotLight {
+    // `cone_angle` and `falloff_angle` are half-angles in degrees; intensity fades out between them.
+    pub fn new(
+        position: Vec3,
+        target: Vec3,
+        intensity: Color,
+        cone_angle: Float,
+        falloff_angle: Float,
+    ) -> Self {
+        let cone_angle = cone_angle.clamp(0.0, 180.0);
+        Self {
+            position,
+            direction: (target - position).normalize(),
+            intensity,
+            cos_cone: cone_angle.to_radians().cos(),
+            cos_falloff_start: falloff_angle.clamp(0.0, cone_angle).to_radians().cos(),
+        }
+    }
+
+    fn falloff(&self, cos_theta: Float) -> Float {
+        if cos_theta >= self.cos_falloff_start {
+            return 1.0;
+        }
+        if cos_theta <= self.cos_cone {
+            return 0.0;
+        }
+        let t = (cos_theta - self.cos_cone) / (self.cos_falloff_start - self.cos_cone);
+        t * t * (3.0 - 2.0 * t)
+    }
+}
+
+impl Light for SpotLight {
+    fn sample_incident(&self, p: &Vec3) -> Option<LightSample> {
+        let offset = self.position - *p;
+        let distance_squared = offset.length_squared();
+        if distance_squared <= 0.0 {
+            return None;
+        }
+        let direction = offset.normalize();
+        let falloff = self.falloff(self.direction.dot(-direction));
+        if falloff <= 0.0 {
+            return None;
+        }
+        Some(LightSample {
+            direction,
+            distance: distance_squared.sqrt(),
+            radiance: self.intensity * falloff / distance_squared,
+            pdf: 1.0,
+        })
+    }
+}
@@ -9,6 +9,13 @@ use bitray::materials::metal::Metal;
 use bitray::quad::Quad;
 use bitray::render_parameters::RenderParameters;
 use bitray::renderers;
+use bitray::renderers::ambient_occlusion::AmbientOcclusionParameters;
+use bitray::renderers::guided::GuidingParameters;
+use bitray::renderers::metropolis::MetropolisParameters;
+use bitray::renderers::photon_mapping::PhotonMappingParameters;
+use bitray::renderers::reservoir::ReservoirParameters;
+use bitray::renderers::RenderMode;
+use bitray::scene::Scene;
 use bitray::sphere::Sphere;
 use bitray::texture::ColorTexture2D;
 use bitray::Vec3;
@@ -117,11 +124,40 @@ fn main() {
             render_params,
         );
 
-        let importants = &light;
-
-        let render_fn = renderers::rayon::render;
+        let scene = Scene::new(&world);
+
+        let render_mode = match std::env::args().nth(1).as_deref() {
+            Some("ao") => RenderMode::AmbientOcclusion(AmbientOcclusionParameters {
+                radius: 100.0,
+                num_samples: 16,
+            }),
+            Some("direct") => RenderMode::DirectLighting,
+            Some("bdpt") => RenderMode::Bidirectional,
+            Some("sppm") => RenderMode::PhotonMapping(PhotonMappingParameters {
+                photons_per_iteration: 200_000,
+                initial_radius: 5.0,
+            }),
+            Some("mlt") => RenderMode::Metropolis(MetropolisParameters {
+                bootstrap_samples: 100_000,
+                chains: 1000,
+                sigma: 0.01,
+                large_step_probability: 0.3,
+            }),
+            Some("guided") => RenderMode::Guided(GuidingParameters {
+                resolution: 16,
+                training_passes: 6,
+            }),
+            Some("restir") => RenderMode::Reservoir(ReservoirParameters {
+                candidates: 32,
+                spatial_neighbors: 5,
+                spatial_radius: 30.0,
+            }),
+            Some("spectral") => RenderMode::Spectral,
+            _ => RenderMode::PathTracing,
+        };
 
-        let scene_render = render_fn(&camera, &world, importants, render_params);
+        let scene_render = renderers::render(render_mode, &camera, &scene, render_params)
+            .expect("Scene should be renderable in the chosen mode");
 
         image_writer::write_image(&scene_render, (1920, 1080), 1500)
             .expect("Image should be writable");
@@ -0,0 +1,252 @@
+use super::material::{Material, MaterialHitResult};
+use crate::color::Color;
+use crate::fresnel;
+use crate::hittable::HitRecord;
+use crate::onb::ONB;
+use crate::pdf::{CosinePDF, WeightedMixturePDF, PDF};
+use crate::rand_vec3::refract;
+use crate::ray::Ray;
+use crate::sampler::random;
+use crate::{Float, Vec3};
+
+// Bounces between the coating and the base followed before giving up on a path.
+const MAX_BOUNCES: usize = 16;
+
+// A smooth dielectric coating over any other material, as on car paint, lacquered wood or
+// varnish. Light reflects off the coating or refracts through it to the base, absorbed on the
+// way by the coating's thickness. Evaluation counts a single scattering off the base and leaves
+// out light the underside of the coating reflects back down, so it is exact for a clear
+// coating only in the limit of a dark base. Bases with a mirror-like surface are followed
+// bouncing under the coating when sampled.
+pub struct Coated<'a> {
+    base: &'a dyn Material,
+    index_of_refraction: Float,
+    // Absorption coefficient of each colour channel times the thickness of the coating.
+    optical_depth: Color,
+}
+
+impl<'a> Coated<'a> {
+    pub fn new(base: &'a dyn Material, index_of_refraction: Float) -> Self {
+        Self {
+            base,
+            index_of_refraction,
+            optical_depth: Color::new(0.0, 0.0, 0.0),
+        }
+    }
+
+    // A coloured coating with absorption coefficient `sigma_a`, `thickness` thick.
+    pub fn with_absorption(mut self, sigma_a: Color, thickness: Float) -> Self {
+        self.optical_depth = sigma_a * thickness.max(0.0);
+        self
+    }
+
+    // Share of light crossing the coating along `w`, in the local frame.
+    fn transmittance(&self, w: Vec3) -> Color {
+        let cos = w.z.abs().max(1.0e-4);
+        Color::new(
+            (-self.optical_depth.r() / cos).exp(),
+            (-self.optical_depth.g() / cos).exp(),
+            (-self.optical_depth.b() / cos).exp(),
+        )
+    }
+
+    // Where light refracts to under the coating, travelling down, from `w` above it.
+    fn refract_in(&self, w: Vec3) -> Vec3 {
+        refract(&-w, &Vec3::Z, 1.0 / self.index_of_refraction)
+    }
+
+    // The base struck by `r_in` once refracted along `direction`, in the local frame.
+    fn base_hit(
+        &self,
+        uvw: &ONB,
+        r_in: &Ray,
+        rec: &HitRecord,
+        direction: Vec3,
+    ) -> (Ray, HitRecord<'a>) {
+        let direction = uvw.transform(&direction);
+        let r = Ray::new(rec.p - direction, direction).with_wavelength(r_in.wavelength);
+        let base_rec = HitRecord::new(rec.p, 1.0, rec.normal, &r, self.base, rec.uv);
+        (r, base_rec)
+    }
+
+    fn outgoing(r_in: &Ray, rec: &HitRecord) -> (ONB, Vec3) {
+        let uvw = ONB::new(&rec.normal);
+        let wo = uvw.to_local(&-r_in.direction.normalize());
+        (uvw, wo)
+    }
+
+    // Follows light bouncing between a base with a mirror-like surface and the coating until it
+    // leaves, giving the direction it leaves in and its colour.
+    fn specular_walk(
+        &self,
+        uvw: &ONB,
+        r_in: &Ray,
+        rec: &HitRecord,
+        down: Vec3,
+    ) -> Option<(Vec3, Color)> {
+        let mut direction = down;
+        let mut beta = self.transmittance(direction);
+        for _ in 0..MAX_BOUNCES {
+            let (r, base_rec) = self.base_hit(uvw, r_in, rec, direction);
+            let result = self.base.scatter(&r, &base_rec)?;
+            if result.pdf.is_some() {
+                return None;
+            }
+            beta = beta * result.color;
+            let up = uvw.to_local(&result.ray.direction.normalize());
+            if up.z <= 0.0 {
+                return None;
+            }
+            beta = beta * self.transmittance(up);
+            let reflectance = fresnel::dielectric(up.z, 1.0 / self.index_of_refraction);
+            if random() >= reflectance {
+                return Some((refract(&up, &-Vec3::Z, self.index_of_refraction), beta));
+            }
+            direction = Vec3::new(up.x, up.y, -up.z);
+            beta = beta * self.transmittance(direction);
+        }
+        None
+    }
+}
+
+impl<'a> Material for Coated<'a> {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<MaterialHitResult> {
+        let (uvw, wo) = Self::outgoing(r_in, rec);
+        if wo.z <= 0.0 {
+            return None;
+        }
+        // The reflection off the coating and the light coming out from under it are picked in
+        // proportion to the Fresnel reflectance.
+        let reflectance = fresnel::dielectric(wo.z, self.index_of_refraction);
+        if random() < reflectance {
+            return Some(MaterialHitResult {
+                color: Color::new(1.0, 1.0, 1.0),
+                ray: Ray::new(rec.p, uvw.transform(&Vec3::new(-wo.x, -wo.y, wo.z))),
+                pdf: None,
+            });
+        }
+
+        let down = self.refract_in(wo);
+        let (r, base_rec) = self.base_hit(&uvw, r_in, rec, down);
+        let base = self.base.scatter(&r, &base_rec)?;
+        let Some(base_pdf) = base.pdf else {
+            let (direction, color) = self.specular_walk(&uvw, r_in, rec, down)?;
+            return Some(MaterialHitResult {
+                color,
+                ray: Ray::new(rec.p, uvw.transform(&direction)),
+                pdf: None,
+            });
+        };
+
+        // Refraction widens the base's lobe, so it is sampled along with the whole hemisphere.
+        let pdf = WeightedMixturePDF::new(vec![
+            (
+                1.0,
+                Box::new(CosinePDF::new(ONB::new(&rec.normal))) as Box<dyn PDF>,
+            ),
+            (1.0, base_pdf),
+        ]);
+        let white = Color::new(1.0, 1.0, 1.0);
+        Some(MaterialHitResult::sampled(
+            white / (1.0 - reflectance),
+            rec,
+            Box::new(pdf),
+        ))
+    }
+
+    // The mixture `scatter` samples from when light is not reflected off the coating.
+    fn sampling_pdf(&self, r_in: &Ray, rec: &HitRecord, scattered_ray: &Ray) -> Option<Float> {
+        let (uvw, wo) = Self::outgoing(r_in, rec);
+        if wo.z <= 0.0 {
+            return None;
+        }
+        let (r, base_rec) = self.base_hit(&uvw, r_in, rec, self.refract_in(wo));
+        let base = self.base.sampling_pdf(&r, &base_rec, scattered_ray)?;
+        let cosine = CosinePDF::new(uvw).value(&scattered_ray.direction);
+        let reflectance = fresnel::dielectric(wo.z, self.index_of_refraction);
+        Some((1.0 - reflectance) * (cosine + base) / 2.0)
+    }
+
+    fn is_dispersive(&self, r_in: &Ray, rec: &HitRecord) -> bool {
+        let (uvw, wo) = Self::outgoing(r_in, rec);
+        let (r, base_rec) = self.base_hit(&uvw, r_in, rec, self.refract_in(wo));
+        self.base.is_dispersive(&r, &base_rec)
+    }
+
+    // Light refracting in from `wo`, scattered once by the base and refracting out towards
+    // `scattered_ray`, less the reflection off the coating.
+    fn scattering_color(
+        &self,
+        r_in: &Ray,
+        rec: &HitRecord,
+        scattered_ray: &Ray,
+        color: Color,
+    ) -> Color {
+        let black = Color::new(0.0, 0.0, 0.0);
+        let (uvw, wo) = Self::outgoing(r_in, rec);
+        let wi = uvw.to_local(&scattered_ray.direction.normalize());
+        if wo.z <= 0.0 || wi.z <= 0.0 {
+            return black;
+        }
+        let down = self.refract_in(wo);
+        let toward_wi = -self.refract_in(wi);
+        let (r, base_rec) = self.base_hit(&uvw, r_in, rec, down);
+        let Some(base) = self.base.scatter(&r, &base_rec) else {
+            return black;
+        };
+        if base.pdf.is_none() {
+            return black;
+        }
+        let to_wi = Ray::new(rec.p, uvw.transform(&toward_wi));
+        let f = self
+            .base
+            .scattering_color(&r, &base_rec, &to_wi, base.color)
+            / toward_wi.z;
+
+        // What crossing the coating in and out keeps, with the solid angle squeezed on the way.
+        let eta = self.index_of_refraction;
+        let transmitted = |w: Vec3| 1.0 - fresnel::dielectric(w.z, eta);
+        let crossing = transmitted(wo) * transmitted(wi) * wi.z / (eta * eta);
+        color * f * self.transmittance(down) * self.transmittance(toward_wi) * crossing
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        materials::{
+            lambert::Lambert,
+            material::tests::{albedo, assert_albedo_within},
+            metal::Metal,
+        },
+        pdf::tests::outgoing,
+        texture::ColorTexture2D,
+    };
+
+    #[test]
+    fn white_furnace() {
+        let white = ColorTexture2D {
+            color: Color::new(1.0, 1.0, 1.0),
+        };
+        let (lambert, rough, mirror) = (
+            Lambert::new(&white),
+            Metal::new(&white, 0.4),
+            Metal::new(&white, 0.0),
+        );
+        // Evaluation leaves out light the coating reflects back down, so bases that scatter
+        // diffusely lose some, while one that is a mirror is followed bouncing exactly.
+        for wo in outgoing() {
+            assert_albedo_within(albedo(&Coated::new(&mirror, 1.5), wo), (0.999, 1.001));
+        }
+        for base in [&lambert as &dyn Material, &rough, &mirror] {
+            let coated = Coated::new(base, 1.5);
+            let tinted = Coated::new(base, 1.5).with_absorption(Color::new(0.0, 1.0, 3.0), 0.5);
+            for wo in outgoing() {
+                let (clear, tinted) = (albedo(&coated, wo), albedo(&tinted, wo));
+                assert_albedo_within(clear, (0.3, 1.01));
+                assert!(tinted.g() < clear.g() && tinted.b() < tinted.g());
+            }
+        }
+    }
+}
@@ -1,57 +1,376 @@
 use super::material::{Material, MaterialHitResult};
+use super::thin_film::ThinFilm;
 use crate::color::Color;
+use crate::fresnel;
+use crate::hittable::HitRecord;
+use crate::microfacet::TrowbridgeReitz;
+use crate::onb::ONB;
+use crate::pdf::{RoughDielectricPDF, PDF};
 use crate::rand_vec3::{reflect, refract};
 use crate::ray::Ray;
-use crate::Float;
-pub struct Dielectric {
+use crate::sampler::random;
+use crate::{Float, Vec3};
+pub struct Dielectric<'a> {
     index_of_refraction: Float,
+    distribution: TrowbridgeReitz,
+    // Absorption coefficient of each colour channel inside.
+    sigma_a: Color,
+    thin_walled: bool,
+    film: Option<ThinFilm<'a>>,
+    abbe_number: Option<Float>,
 }
 
-impl Material for Dielectric {
+impl<'a> Material for Dielectric<'a> {
     fn scatter(
         &self,
         r_in: &crate::ray::Ray,
         rec: &crate::hittable::HitRecord,
     ) -> Option<MaterialHitResult> {
-        let refraction_ratio = if rec.front_face {
-            1.0 / self.index_of_refraction
+        let unit_direction = r_in.direction.normalize();
+        if self.thin_walled {
+            let (direction, color) = self.thin_wall_direction(unit_direction, r_in.wavelength, rec);
+            return Some(MaterialHitResult {
+                color,
+                ray: Ray::new(rec.p, direction),
+                pdf: None,
+            });
+        }
+
+        // Rays reaching the boundary from inside have been absorbed along the way there.
+        let color = if rec.front_face {
+            Color::new(1.0, 1.0, 1.0)
         } else {
-            self.index_of_refraction
+            let distance = rec.t * r_in.direction.length();
+            Color::new(
+                (-self.sigma_a.r() * distance).exp(),
+                (-self.sigma_a.g() * distance).exp(),
+                (-self.sigma_a.b() * distance).exp(),
+            )
         };
-        let unit_direction = r_in.direction.normalize();
 
-        let cos_theta = Float::min(rec.normal.dot(-unit_direction), 1.0);
-        let sin_theta = Float::sqrt(1.0 - cos_theta * cos_theta);
+        if self.distribution.is_smooth() {
+            let (direction, tint) = self.smooth_direction(unit_direction, r_in.wavelength, rec);
+            return Some(MaterialHitResult {
+                color: color * tint,
+                ray: Ray::new(rec.p, direction),
+                pdf: None,
+            });
+        }
 
-        let cannot_refract = refraction_ratio * sin_theta > 1.0;
+        let uvw = ONB::new(&rec.normal);
+        let wo = uvw.to_local(&-unit_direction);
+        let pdf = Box::new(RoughDielectricPDF::new(
+            uvw,
+            wo,
+            self.relative_index(r_in.wavelength, rec),
+            self.distribution,
+        ));
+        Some(MaterialHitResult::sampled(color, rec, pdf))
+    }
 
-        let direction =
-            if cannot_refract || Self::reflectance(cos_theta, refraction_ratio) > rand::random() {
-                reflect(&unit_direction, &rec.normal)
-            } else {
-                refract(&unit_direction, &rec.normal, refraction_ratio)
-            };
+    // Walter et al.'s microfacet reflection and transmission, less the colour of absorption.
+    fn scattering_pdf(&self, r_in: &Ray, rec: &HitRecord, scattered_ray: &Ray) -> Float {
+        let uvw = ONB::new(&rec.normal);
+        let wo = uvw.to_local(&-r_in.direction.normalize());
+        let wi = uvw.to_local(&scattered_ray.direction.normalize());
+        Self::rough_scattering(
+            wo,
+            wi,
+            self.relative_index(r_in.wavelength, rec),
+            &self.distribution,
+        )
+    }
 
-        let scattered = Ray::new(rec.p, direction);
-        return Some(MaterialHitResult {
-            color: Color::new(1.0, 1.0, 1.0),
-            ray: scattered,
-            pdf: None,
-        });
+    fn sampling_pdf(&self, r_in: &Ray, rec: &HitRecord, scattered_ray: &Ray) -> Option<Float> {
+        if self.thin_walled || self.distribution.is_smooth() {
+            return None;
+        }
+        let uvw = ONB::new(&rec.normal);
+        let wo = uvw.to_local(&-r_in.direction.normalize());
+        let eta = self.relative_index(r_in.wavelength, rec);
+        let pdf = RoughDielectricPDF::new(uvw, wo, eta, self.distribution);
+        Some(pdf.value(&scattered_ray.direction))
+    }
+
+    fn is_dispersive(&self, r_in: &Ray, _rec: &HitRecord) -> bool {
+        r_in.wavelength.is_some() && (self.abbe_number.is_some() || self.film.is_some())
+    }
+
+    // A film's reflectance changes colour with the microfacet, so it is taken here.
+    fn scattering_color(
+        &self,
+        r_in: &Ray,
+        rec: &HitRecord,
+        scattered_ray: &Ray,
+        color: Color,
+    ) -> Color {
+        if self.film.is_none() {
+            return color * self.scattering_pdf(r_in, rec, scattered_ray);
+        }
+        let uvw = ONB::new(&rec.normal);
+        let wo = uvw.to_local(&-r_in.direction.normalize());
+        let wi = uvw.to_local(&scattered_ray.direction.normalize());
+        let eta = self.relative_index(r_in.wavelength, rec);
+        let Some((wm, dg)) = Self::rough_terms(wo, wi, eta, &self.distribution) else {
+            return Color::new(0.0, 0.0, 0.0);
+        };
+        let reflectance = self.reflectance(wo.dot(wm), r_in.wavelength, rec);
+        if wi.z > 0.0 {
+            color * reflectance * dg
+        } else {
+            color * complement(reflectance) * dg
+        }
     }
 }
 
-impl Dielectric {
+impl<'a> Dielectric<'a> {
     pub fn new(index_of_refraction: Float) -> Self {
         Self {
             index_of_refraction,
+            distribution: TrowbridgeReitz::new(0.0, 0.0),
+            sigma_a: Color::new(0.0, 0.0, 0.0),
+            thin_walled: false,
+            film: None,
+            abbe_number: None,
+        }
+    }
+
+    // Frosted rather than clear, from a mirror-like surface at zero to very rough at one.
+    pub fn with_roughness(mut self, roughness: Float) -> Self {
+        let alpha = TrowbridgeReitz::roughness_to_alpha(roughness);
+        self.distribution = TrowbridgeReitz::new(alpha, alpha);
+        self
+    }
+
+    // Coloured glass, which lets through `transmittance` of the light travelling `distance`
+    // inside it.
+    pub fn with_absorption(mut self, transmittance: Color, distance: Float) -> Self {
+        let sigma = |t: Float| -t.clamp(1.0e-6, 1.0).ln() / distance.max(1.0e-6);
+        self.sigma_a = Color::new(
+            sigma(transmittance.r()),
+            sigma(transmittance.g()),
+            sigma(transmittance.b()),
+        );
+        self
+    }
+
+    // An infinitely thin sheet, such as a window pane or a bubble, which light crosses without
+    // bending. Roughness and absorption do not apply.
+    pub fn thin_walled(mut self) -> Self {
+        self.thin_walled = true;
+        self
+    }
+
+    // Glass whose index of refraction falls with wavelength, splitting white light into colours
+    // when rendering spectrally. Flint glass has an Abbe number around 30 and crown glass around
+    // 60; lower numbers disperse more.
+    pub fn with_dispersion(mut self, abbe_number: Float) -> Self {
+        self.abbe_number = Some(abbe_number.max(1.0e-3));
+        self
+    }
+
+    // Coated with `film` on the outside, which tints reflection and transmission alike.
+    pub fn with_thin_film(mut self, film: ThinFilm<'a>) -> Self {
+        self.film = Some(film);
+        self
+    }
+
+    // Reflection and transmission of a rough boundary times the cosine, between directions in
+    // the local frame of the surface with `wo` above it.
+    pub(crate) fn rough_scattering(
+        wo: Vec3,
+        wi: Vec3,
+        eta: Float,
+        distribution: &TrowbridgeReitz,
+    ) -> Float {
+        let Some((wm, dg)) = Self::rough_terms(wo, wi, eta, distribution) else {
+            return 0.0;
+        };
+        let reflectance = fresnel::dielectric(wo.dot(wm), eta);
+        if wi.z > 0.0 {
+            reflectance * dg
+        } else {
+            (1.0 - reflectance) * dg
+        }
+    }
+
+    // The microfacet scattering between `wo` and `wi` and the rest of `rough_scattering` but for
+    // the Fresnel term.
+    fn rough_terms(
+        wo: Vec3,
+        wi: Vec3,
+        eta: Float,
+        distribution: &TrowbridgeReitz,
+    ) -> Option<(Vec3, Float)> {
+        let wm = RoughDielectricPDF::half_vector(wo, wi, eta)?;
+        let dg = distribution.d(wm) * distribution.g(wo, wi);
+        if wi.z > 0.0 {
+            Some((wm, dg / (4.0 * wo.z)))
+        } else {
+            let denom = wi.dot(wm) + wo.dot(wm) / eta;
+            Some((
+                wm,
+                dg * (wi.dot(wm) * wo.dot(wm)).abs() / (wo.z * denom * denom),
+            ))
+        }
+    }
+
+    // Reflectance of the boundary from the side `rec` was hit on, at `cos_theta` to the normal.
+    fn reflectance(&self, cos_theta: Float, lambda: Option<Float>, rec: &HitRecord) -> Color {
+        let Some(film) = &self.film else {
+            let r = fresnel::dielectric(cos_theta, self.relative_index(lambda, rec));
+            return Color::new(r, r, r);
+        };
+        let (outside, inside) = if rec.front_face {
+            (1.0, self.index(lambda))
+        } else {
+            (self.index(lambda), 1.0)
+        };
+        let black = Color::new(0.0, 0.0, 0.0);
+        film.reflectance(
+            rec.uv,
+            cos_theta,
+            outside,
+            Color::new(inside, inside, inside),
+            black,
+            lambda,
+        )
+    }
+
+    // Index of refraction on the far side of the boundary relative to the side the ray is on.
+    fn relative_index(&self, lambda: Option<Float>, rec: &HitRecord) -> Float {
+        if rec.front_face {
+            self.index(lambda)
+        } else {
+            1.0 / self.index(lambda)
         }
     }
 
-    fn reflectance(cosine: Float, ref_idx: Float) -> Float {
-        // Use Schlick's approximation for reflectance.
-        let r0 = (1.0 - ref_idx) / (1.0 + ref_idx);
-        let r0 = r0 * r0;
-        return r0 + (1.0 - r0) * Float::powf(1.0 - cosine, 5.0);
+    // The index of refraction at wavelength `lambda`, when rendering spectrally. Cauchy's
+    // equation spreads it about the Fraunhofer d line, where it is `index_of_refraction`.
+    fn index(&self, lambda: Option<Float>) -> Float {
+        let (Some(abbe_number), Some(lambda)) = (self.abbe_number, lambda) else {
+            return self.index_of_refraction;
+        };
+        let (d, f, c): (Float, Float, Float) = (587.6, 486.1, 656.3);
+        let b = (self.index_of_refraction - 1.0) / (abbe_number * (1.0 / (f * f) - 1.0 / (c * c)));
+        self.index_of_refraction + b * (1.0 / (lambda * lambda) - 1.0 / (d * d))
+    }
+
+    // A film coats only the side of the sheet rays arrive on, so a bubble is a sheet of index
+    // one with a film of water.
+    fn thin_wall_direction(
+        &self,
+        unit_direction: Vec3,
+        lambda: Option<Float>,
+        rec: &HitRecord,
+    ) -> (Vec3, Color) {
+        let cos_theta = Float::min(rec.normal.dot(-unit_direction), 1.0);
+        let near = match &self.film {
+            Some(film) => {
+                let ior = self.index_of_refraction;
+                let black = Color::new(0.0, 0.0, 0.0);
+                let inside = Color::new(ior, ior, ior);
+                film.reflectance(rec.uv, cos_theta, 1.0, inside, black, lambda)
+            }
+            None => {
+                let r = fresnel::dielectric(cos_theta, self.index_of_refraction);
+                Color::new(r, r, r)
+            }
+        };
+        let far = fresnel::dielectric(cos_theta, self.index_of_refraction);
+        // Light bouncing back and forth between the two sides adds to what each lets through.
+        let sheet = |near: Float| {
+            if near * far < 1.0 {
+                near + (1.0 - near) * (1.0 - near) * far / (1.0 - near * far)
+            } else {
+                1.0
+            }
+        };
+        let reflectance = Color::new(sheet(near.r()), sheet(near.g()), sheet(near.b()));
+        Self::choose(
+            reflectance,
+            reflect(&unit_direction, &rec.normal),
+            unit_direction,
+        )
+    }
+
+    fn smooth_direction(
+        &self,
+        unit_direction: Vec3,
+        lambda: Option<Float>,
+        rec: &HitRecord,
+    ) -> (Vec3, Color) {
+        let cos_theta = Float::min(rec.normal.dot(-unit_direction), 1.0);
+        let refraction_ratio = 1.0 / self.relative_index(lambda, rec);
+        let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
+        if refraction_ratio * sin_theta > 1.0 {
+            let white = Color::new(1.0, 1.0, 1.0);
+            return (reflect(&unit_direction, &rec.normal), white);
+        }
+        Self::choose(
+            self.reflectance(cos_theta, lambda, rec),
+            reflect(&unit_direction, &rec.normal),
+            refract(&unit_direction, &rec.normal, refraction_ratio),
+        )
+    }
+
+    // Picks reflection or transmission with the average probability of each over the channels,
+    // along with the colour that makes up for the difference.
+    fn choose(reflectance: Color, reflected: Vec3, transmitted: Vec3) -> (Vec3, Color) {
+        let p = (reflectance.r() + reflectance.g() + reflectance.b()) / 3.0;
+        if p > random() {
+            (reflected, reflectance / p)
+        } else {
+            (transmitted, complement(reflectance) / (1.0 - p))
+        }
+    }
+
+    // Direction of a ray reflected or refracted at the surface of a dielectric, chosen with the
+    // probability of each.
+    pub(crate) fn boundary_direction(
+        unit_direction: Vec3,
+        rec: &HitRecord,
+        index_of_refraction: Float,
+    ) -> Vec3 {
+        let refraction_ratio = if rec.front_face {
+            1.0 / index_of_refraction
+        } else {
+            index_of_refraction
+        };
+
+        let cos_theta = Float::min(rec.normal.dot(-unit_direction), 1.0);
+
+        if fresnel::dielectric(cos_theta, 1.0 / refraction_ratio) > random() {
+            reflect(&unit_direction, &rec.normal)
+        } else {
+            refract(&unit_direction, &rec.normal, refraction_ratio)
+        }
+    }
+}
+
+fn complement(c: Color) -> Color {
+    Color::new(1.0 - c.r(), 1.0 - c.g(), 1.0 - c.b())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        materials::material::tests::{albedo, assert_albedo_within},
+        pdf::tests::outgoing,
+    };
+
+    #[test]
+    fn white_furnace() {
+        // Light arriving from outside and from inside the surface.
+        let directions = outgoing().into_iter().flat_map(|wo| [wo, -wo]);
+        for wo in directions {
+            assert_albedo_within(albedo(&Dielectric::new(1.5), wo), (0.98, 1.02));
+            let rough = Dielectric::new(1.5).with_roughness(0.2);
+            assert_albedo_within(albedo(&rough, wo), (0.9, 1.02));
+            let rougher = Dielectric::new(1.5).with_roughness(0.7);
+            assert_albedo_within(albedo(&rougher, wo), (0.2, 1.02));
+        }
     }
 }
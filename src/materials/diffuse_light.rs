@@ -1,14 +1,108 @@
-use crate::{color::Color, texture::Sampler2D};
+use crate::{
+    color::Color,
+    lights::ies::IesProfile,
+    onb::ONB,
+    spectrum::{blackbody, LUMINOUS_EFFICACY},
+    texture::Sampler2D,
+    Float, Vec3, PI,
+};
 
 use super::material::Material;
 
 pub struct DiffuseLightMaterial<'a> {
     color: &'a dyn Sampler2D,
+    tint: Color,
+    one_sided: bool,
+    // Radiant exitance per unit of texture luminance, over both sides together.
+    exitance: Option<Float>,
+    profile: Option<&'a IesProfile>,
+    // Integrals of the profile times the cosine over the hemispheres in front of and behind the
+    // surface, which are both PI without a profile.
+    projected: (Float, Float),
+    // Brightest intensity of the profile, which it is shown relative to.
+    max_candela: Float,
 }
 
 impl<'a> DiffuseLightMaterial<'a> {
     pub fn new(color: &'a dyn Sampler2D) -> Self {
-        Self { color }
+        Self {
+            color,
+            tint: Color::new(1.0, 1.0, 1.0),
+            one_sided: false,
+            exitance: None,
+            profile: None,
+            projected: (PI, PI),
+            max_candela: 1.0,
+        }
+    }
+
+    // Emits only from the side the surface normal points to.
+    pub fn one_sided(self) -> Self {
+        Self {
+            one_sided: true,
+            ..self
+        }
+    }
+
+    // Tints the emission with the colour of a black body at `kelvin`, keeping its luminance.
+    pub fn with_temperature(self, kelvin: Float) -> Self {
+        Self {
+            tint: self.tint * blackbody(kelvin),
+            ..self
+        }
+    }
+
+    // Scales the emission so that every unit of area radiates `watts`, taking the texture to have
+    // a luminance of one on average. A surface radiates this times its area in total.
+    pub fn with_exitance(self, watts: Float) -> Self {
+        Self {
+            exitance: Some(watts),
+            ..self
+        }
+    }
+
+    // `with_exitance` in lumens per unit of area.
+    pub fn with_luminous_exitance(self, lumens: Float) -> Self {
+        self.with_exitance(lumens / LUMINOUS_EFFICACY)
+    }
+
+    // Shapes the emission by a photometric profile whose nadir is the surface normal, relative to
+    // its brightest direction. An exitance set alongside is kept by brightening the surface.
+    pub fn with_profile(self, profile: &'a IesProfile) -> Self {
+        let max = profile.max_candela();
+        let (thetas, phis) = (180, 72);
+        let (d_theta, d_phi) = (PI / thetas as Float, 2.0 * PI / phis as Float);
+        let mut projected = (0.0, 0.0);
+        for i in 0..thetas {
+            let theta = (i as Float + 0.5) * d_theta;
+            let ring = (0..phis).fold(0.0, |sum, j| {
+                let phi = (j as Float + 0.5) * d_phi;
+                sum + profile.candela(theta.to_degrees(), phi.to_degrees())
+            });
+            let solid = ring / max * theta.cos().abs() * theta.sin() * d_theta * d_phi;
+            if i < thetas / 2 {
+                projected.0 += solid;
+            } else {
+                projected.1 += solid;
+            }
+        }
+        Self {
+            profile: Some(profile),
+            projected,
+            max_candela: max,
+            ..self
+        }
+    }
+
+    // How much of the profile's brightest intensity leaves towards `direction`.
+    fn profile_scale(&self, outward: &Vec3, direction: &Vec3) -> Float {
+        let Some(profile) = self.profile else {
+            return 1.0;
+        };
+        let local = ONB::new(outward).to_local(&direction.normalize());
+        let vertical = local.z.clamp(-1.0, 1.0).acos().to_degrees();
+        let horizontal = local.y.atan2(local.x).to_degrees();
+        profile.candela(vertical, horizontal) / self.max_candela
     }
 }
 
@@ -21,7 +115,23 @@ impl<'a> Material for DiffuseLightMaterial<'a> {
         None
     }
 
-    fn emit_color(&self, _: &crate::ray::Ray, hc: &crate::hittable::HitRecord) -> Color {
-        self.color.sample(hc.uv)
+    fn emit_color(&self, r: &crate::ray::Ray, hc: &crate::hittable::HitRecord) -> Color {
+        if self.one_sided && !hc.front_face {
+            return Color::new(0.0, 0.0, 0.0);
+        }
+        let outward = if hc.front_face { hc.normal } else { -hc.normal };
+        let emitted =
+            self.color.sample(hc.uv) * self.tint * self.profile_scale(&outward, &-r.direction);
+        let (front, back) = self.projected;
+        let projected = if self.one_sided { front } else { front + back };
+        match self.exitance {
+            Some(exitance) if projected > 0.0 => emitted * (exitance / projected),
+            Some(_) => Color::new(0.0, 0.0, 0.0),
+            None => emitted,
+        }
+    }
+
+    fn is_emissive(&self) -> bool {
+        true
     }
 }
@@ -0,0 +1,35 @@
+use super::material::{Material, MaterialHitResult};
+use crate::color::Color;
+use crate::hittable::HitRecord;
+use crate::pdf::HenyeyGreensteinPDF;
+use crate::ray::Ray;
+use crate::Float;
+
+// Scattering inside a participating medium. `g` above zero favours light carrying on forwards,
+// below zero sends it back; zero scatters equally in all directions.
+pub struct HenyeyGreenstein {
+    albedo: Color,
+    g: Float,
+}
+
+impl HenyeyGreenstein {
+    pub fn new(albedo: Color, g: Float) -> Self {
+        Self { albedo, g }
+    }
+}
+
+impl Material for HenyeyGreenstein {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<MaterialHitResult> {
+        let pdf = Box::new(HenyeyGreensteinPDF::new(&r_in.direction, self.g));
+        Some(MaterialHitResult::sampled(self.albedo, rec, pdf))
+    }
+
+    fn scattering_pdf(&self, r_in: &Ray, _rec: &HitRecord, scattered_ray: &Ray) -> Float {
+        HenyeyGreensteinPDF::phase(
+            self.g.clamp(-0.99, 0.99),
+            r_in.direction
+                .normalize()
+                .dot(scattered_ray.direction.normalize()),
+        )
+    }
+}
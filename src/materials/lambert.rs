@@ -1,6 +1,6 @@
 use super::material::{Material, MaterialHitResult};
 use crate::onb::ONB;
-use crate::pdf::{CosinePDF, PDF};
+use crate::pdf::CosinePDF;
 use crate::ray::Ray;
 use crate::texture::Sampler2D;
 use crate::{Float, PI};
@@ -15,11 +15,11 @@ impl<'a> Material for Lambert<'a> {
         rec: &crate::hittable::HitRecord,
     ) -> Option<super::material::MaterialHitResult> {
         let pdf = Box::new(CosinePDF::new(ONB::new(&rec.normal)));
-        return Some(MaterialHitResult {
-            color: self.albedo.sample(rec.uv),
-            ray: Ray::new(rec.p, pdf.generate()),
-            pdf: Some(pdf),
-        });
+        return Some(MaterialHitResult::sampled(
+            self.albedo.sample(rec.uv),
+            rec,
+            pdf,
+        ));
     }
 
     fn scattering_pdf(
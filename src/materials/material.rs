@@ -8,6 +8,19 @@ pub struct MaterialHitResult {
     pub ray: Ray,
     pub pdf: Option<Box<dyn PDF>>,
 }
+
+impl MaterialHitResult {
+    // Scattering in directions drawn from `pdf`, which renderers sample for themselves. `ray` is
+    // one such direction, or along the normal when the draw found none.
+    pub fn sampled(color: Color, rec: &HitRecord, pdf: Box<dyn PDF>) -> Self {
+        let direction = pdf.generate().unwrap_or(rec.normal);
+        Self {
+            color,
+            ray: Ray::new(rec.p, direction),
+            pdf: Some(pdf),
+        }
+    }
+}
 pub trait Material: Send + Sync {
     fn scatter(&self, _r_in: &Ray, _rec: &HitRecord) -> Option<MaterialHitResult> {
         None
@@ -18,4 +31,80 @@ pub trait Material: Send + Sync {
     fn scattering_pdf(&self, _r_in: &Ray, _hit_record: &HitRecord, _scattered_ray: &Ray) -> Float {
         0.0
     }
+    // Light scattered along `scattered_ray` per unit arriving from it, cosine included, where
+    // `color` came from `scatter`. Materials whose colour changes with direction override this.
+    fn scattering_color(
+        &self,
+        r_in: &Ray,
+        hit_record: &HitRecord,
+        scattered_ray: &Ray,
+        color: Color,
+    ) -> Color {
+        color * self.scattering_pdf(r_in, hit_record, scattered_ray)
+    }
+    // Density with which `scatter` picks `scattered_ray`, or `None` when it only picks specular
+    // directions. That is `scattering_pdf` for materials sampling in proportion to it, and
+    // others override this. Materials choosing between specular and sampled directions at
+    // random give the density over both choices, so that the answer does not depend on one.
+    fn sampling_pdf(
+        &self,
+        r_in: &Ray,
+        hit_record: &HitRecord,
+        scattered_ray: &Ray,
+    ) -> Option<Float> {
+        Some(self.scattering_pdf(r_in, hit_record, scattered_ray))
+    }
+    fn is_emissive(&self) -> bool {
+        false
+    }
+    // Whether scattering `r_in` depends on the wavelength it carries, so that light scattered
+    // here is only known at that wavelength.
+    fn is_dispersive(&self, _r_in: &Ray, _hit_record: &HitRecord) -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::{Vec2, Vec3};
+
+    // Fraction of the light arriving from `wo` that `material` scatters, at a surface through the
+    // origin facing up, estimated the way renderers sample it.
+    pub(crate) fn albedo(material: &dyn Material, wo: Vec3) -> Color {
+        let samples = 200_000;
+        let r_in = Ray::new(wo, -wo);
+        let rec = HitRecord::new(Vec3::ZERO, 1.0, Vec3::Z, &r_in, material, Vec2::ZERO);
+        let mut total = Color::new(0.0, 0.0, 0.0);
+        for _ in 0..samples {
+            let Some(hit) = material.scatter(&r_in, &rec) else {
+                continue;
+            };
+            let Some(pdf) = hit.pdf else {
+                total += hit.color;
+                continue;
+            };
+            let Some(direction) = pdf.generate() else {
+                continue;
+            };
+            let value = pdf.value(&direction);
+            if value > 0.0 {
+                let scattered = Ray::new(rec.p, direction);
+                total += material.scattering_color(&r_in, &rec, &scattered, hit.color) / value;
+            }
+        }
+        total / samples as Float
+    }
+
+    // Checks that every channel of `albedo` lies within `range`.
+    pub(crate) fn assert_albedo_within(albedo: Color, range: (Float, Float)) {
+        for channel in [albedo.r(), albedo.g(), albedo.b()] {
+            assert!(
+                channel >= range.0 && channel <= range.1,
+                "Scattered {:?}, outside {:?}",
+                albedo,
+                range
+            );
+        }
+    }
 }
@@ -1,38 +1,233 @@
 use crate::{
-    rand_vec3::{random_unit_vector, reflect},
+    color::Color,
+    fresnel,
+    hittable::HitRecord,
+    microfacet::TrowbridgeReitz,
+    onb::ONB,
+    pdf::{MicrofacetPDF, PDF},
+    rand_vec3::reflect,
     ray::Ray,
     texture::Sampler2D,
-    Float,
+    Float, Vec3,
 };
 
 use super::material::{Material, MaterialHitResult};
+use super::thin_film::ThinFilm;
 
+enum Reflectance<'a> {
+    // Tinted by the colour seen head on, with Schlick's approximation towards grazing angles.
+    Albedo(&'a dyn Sampler2D),
+    // Measured complex index of refraction.
+    Conductor { eta: Color, k: Color },
+}
+
+// A conductor with microfacets following the GGX distribution. The tangent of anisotropic
+// roughness follows the first axis of `ONB::new` around the normal.
 pub struct Metal<'a> {
-    albedo: &'a dyn Sampler2D,
-    fuzz: Float,
+    reflectance: Reflectance<'a>,
+    distribution: TrowbridgeReitz,
+    film: Option<ThinFilm<'a>>,
+}
+
+impl<'a> Metal<'a> {
+    // `roughness` runs from a perfect mirror at zero to a very rough surface at one.
+    pub fn new(albedo: &'a dyn Sampler2D, roughness: Float) -> Self {
+        Self::build(Reflectance::Albedo(albedo), roughness)
+    }
+
+    // A conductor of complex index of refraction `eta + i k` in each colour channel.
+    pub fn conductor(eta: Color, k: Color, roughness: Float) -> Self {
+        Self::build(Reflectance::Conductor { eta, k }, roughness)
+    }
+
+    pub fn gold(roughness: Float) -> Self {
+        Self::conductor(
+            Color::new(0.143, 0.374, 1.442),
+            Color::new(3.983, 2.385, 1.603),
+            roughness,
+        )
+    }
+
+    pub fn copper(roughness: Float) -> Self {
+        Self::conductor(
+            Color::new(0.200, 0.924, 1.102),
+            Color::new(3.912, 2.452, 2.142),
+            roughness,
+        )
+    }
+
+    pub fn aluminium(roughness: Float) -> Self {
+        Self::conductor(
+            Color::new(1.657, 0.880, 0.521),
+            Color::new(9.224, 6.270, 4.837),
+            roughness,
+        )
+    }
+
+    pub fn silver(roughness: Float) -> Self {
+        Self::conductor(
+            Color::new(0.155, 0.117, 0.138),
+            Color::new(4.828, 3.122, 2.147),
+            roughness,
+        )
+    }
+
+    // Different roughness along the tangent and the bitangent, as on brushed metal.
+    pub fn with_anisotropic_roughness(mut self, roughness_u: Float, roughness_v: Float) -> Self {
+        self.distribution = TrowbridgeReitz::new(
+            TrowbridgeReitz::roughness_to_alpha(roughness_u),
+            TrowbridgeReitz::roughness_to_alpha(roughness_v),
+        );
+        self
+    }
+
+    // Coated with `film`, as on heat-tinted steel or titanium.
+    pub fn with_thin_film(mut self, film: ThinFilm<'a>) -> Self {
+        self.film = Some(film);
+        self
+    }
+
+    fn build(reflectance: Reflectance<'a>, roughness: Float) -> Self {
+        let alpha = TrowbridgeReitz::roughness_to_alpha(roughness);
+        Self {
+            reflectance,
+            distribution: TrowbridgeReitz::new(alpha, alpha),
+            film: None,
+        }
+    }
+
+    // Reflectance at `cos_theta` to the microfacet, at wavelength `lambda` when rendering
+    // spectrally.
+    fn fresnel(&self, cos_theta: Float, lambda: Option<Float>, rec: &HitRecord) -> Color {
+        let Some(film) = &self.film else {
+            return match &self.reflectance {
+                Reflectance::Albedo(albedo) => fresnel::schlick(albedo.sample(rec.uv), cos_theta),
+                Reflectance::Conductor { eta, k } => match lambda {
+                    Some(lambda) => {
+                        let (eta, k) = (
+                            fresnel::channel_at(*eta, lambda),
+                            fresnel::channel_at(*k, lambda),
+                        );
+                        fresnel::conductor(
+                            cos_theta,
+                            Color::new(eta, eta, eta),
+                            Color::new(k, k, k),
+                        )
+                    }
+                    None => fresnel::conductor(cos_theta, *eta, *k),
+                },
+            };
+        };
+        let (eta, k) = match &self.reflectance {
+            // Under a film the albedo is taken as the reflectance of a dielectric head on.
+            Reflectance::Albedo(albedo) => {
+                let f0 = albedo.sample(rec.uv);
+                let eta = |f0: Float| {
+                    let r = f0.clamp(0.0, 0.99).sqrt();
+                    (1.0 + r) / (1.0 - r)
+                };
+                (
+                    Color::new(eta(f0.r()), eta(f0.g()), eta(f0.b())),
+                    Color::new(0.0, 0.0, 0.0),
+                )
+            }
+            Reflectance::Conductor { eta, k } => (*eta, *k),
+        };
+        film.reflectance(rec.uv, cos_theta, 1.0, eta, k, lambda)
+    }
+
+    fn outgoing(r_in: &Ray, rec: &HitRecord) -> (ONB, Vec3) {
+        let uvw = ONB::new(&rec.normal);
+        let wo = uvw.to_local(&-r_in.direction.normalize());
+        (uvw, wo)
+    }
 }
 
 impl<'a> Material for Metal<'a> {
-    fn scatter(
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<MaterialHitResult> {
+        let (uvw, wo) = Self::outgoing(r_in, rec);
+        if wo.z <= 0.0 {
+            return None;
+        }
+        if self.distribution.is_smooth() {
+            return Some(MaterialHitResult {
+                color: self.fresnel(wo.z, r_in.wavelength, rec),
+                ray: Ray::new(rec.p, reflect(&r_in.direction.normalize(), &rec.normal)),
+                pdf: None,
+            });
+        }
+        let pdf = Box::new(MicrofacetPDF::new(uvw, wo, self.distribution));
+        Some(MaterialHitResult::sampled(
+            Color::new(1.0, 1.0, 1.0),
+            rec,
+            pdf,
+        ))
+    }
+
+    fn sampling_pdf(&self, r_in: &Ray, rec: &HitRecord, scattered_ray: &Ray) -> Option<Float> {
+        let (uvw, wo) = Self::outgoing(r_in, rec);
+        if wo.z <= 0.0 || self.distribution.is_smooth() {
+            return None;
+        }
+        Some(MicrofacetPDF::new(uvw, wo, self.distribution).value(&scattered_ray.direction))
+    }
+
+    fn is_dispersive(&self, r_in: &Ray, _rec: &HitRecord) -> bool {
+        let conductor = matches!(self.reflectance, Reflectance::Conductor { .. });
+        r_in.wavelength.is_some() && (conductor || self.film.is_some())
+    }
+
+    // The Fresnel colour depends on the microfacet, and so on the scattered direction.
+    fn scattering_color(
         &self,
         r_in: &Ray,
-        rec: &crate::hittable::HitRecord,
-    ) -> Option<super::material::MaterialHitResult> {
-        let reflected = reflect(&r_in.direction, &rec.normal);
-        let scattered = Ray::new(rec.p, reflected + self.fuzz * random_unit_vector());
-        if rec.normal.dot(scattered.direction) < 0.0 {
-            return None;
+        rec: &HitRecord,
+        scattered_ray: &Ray,
+        color: Color,
+    ) -> Color {
+        let (uvw, wo) = Self::outgoing(r_in, rec);
+        let wi = uvw.to_local(&scattered_ray.direction.normalize());
+        if wo.z <= 0.0 || wi.z <= 0.0 {
+            return Color::new(0.0, 0.0, 0.0);
         }
-        return Some(MaterialHitResult {
-            color: self.albedo.sample(rec.uv),
-            ray: scattered,
-            pdf: None,
-        });
+        let wm = (wo + wi).normalize();
+        color
+            * self.fresnel(wo.dot(wm), r_in.wavelength, rec)
+            * (self.distribution.d(wm) * self.distribution.g(wo, wi) / (4.0 * wo.z))
     }
 }
 
-impl<'a> Metal<'a> {
-    pub fn new(albedo: &'a dyn Sampler2D, fuzz: Float) -> Self {
-        return Self { albedo, fuzz };
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        materials::material::tests::{albedo, assert_albedo_within},
+        pdf::tests::outgoing,
+        texture::ColorTexture2D,
+    };
+
+    #[test]
+    fn white_furnace() {
+        let white = ColorTexture2D {
+            color: Color::new(1.0, 1.0, 1.0),
+        };
+        for wo in outgoing() {
+            assert_albedo_within(albedo(&Metal::new(&white, 0.0), wo), (0.999, 1.001));
+            assert_albedo_within(albedo(&Metal::new(&white, 0.1), wo), (0.95, 1.01));
+            for roughness in [0.5, 1.0] {
+                assert_albedo_within(albedo(&Metal::new(&white, roughness), wo), (0.2, 1.01));
+            }
+            let brushed = Metal::new(&white, 0.0).with_anisotropic_roughness(0.6, 0.1);
+            assert_albedo_within(albedo(&brushed, wo), (0.2, 1.01));
+        }
+    }
+
+    #[test]
+    fn conductors_absorb() {
+        for wo in outgoing() {
+            for roughness in [0.0, 0.3] {
+                assert_albedo_within(albedo(&Metal::gold(roughness), wo), (0.0, 1.0));
+            }
+        }
     }
 }
@@ -1,5 +1,11 @@
+pub mod coated;
 pub mod dielectric;
 pub mod diffuse_light;
+pub mod henyey_greenstein;
 pub mod lambert;
 pub mod material;
 pub mod metal;
+pub mod oren_nayar;
+pub mod principled;
+pub mod subsurface;
+pub mod thin_film;
@@ -0,0 +1,169 @@
+use super::material::{Material, MaterialHitResult};
+use crate::color::Color;
+use crate::hittable::HitRecord;
+use crate::microfacet::{Charlie, TrowbridgeReitz};
+use crate::onb::ONB;
+use crate::pdf::{CharliePDF, CosinePDF, WeightedMixturePDF, PDF};
+use crate::ray::Ray;
+use crate::texture::Sampler2D;
+use crate::{Float, Vec3, PI};
+
+const CONSTANT1: Float = 0.5 - 2.0 / (3.0 * PI);
+const CONSTANT2: Float = 2.0 / 3.0 - 28.0 / (15.0 * PI);
+
+// Rough diffuse surfaces such as clay or concrete, which look flatter than `Lambert` and reflect
+// more back towards the light. This is Portsmouth et al.'s energy-preserving Oren-Nayar model,
+// whose light lost to the rough surface shadowing itself is added back as multiple scattering.
+// Fabrics may add a sheen of fibres catching light at grazing angles.
+pub struct OrenNayar<'a> {
+    albedo: &'a dyn Sampler2D,
+    roughness: &'a dyn Sampler2D,
+    sheen: Option<(&'a dyn Sampler2D, &'a dyn Sampler2D)>,
+}
+
+impl<'a> OrenNayar<'a> {
+    // `roughness` is read from the luminance of its texture, from Lambert at zero to one.
+    pub fn new(albedo: &'a dyn Sampler2D, roughness: &'a dyn Sampler2D) -> Self {
+        Self {
+            albedo,
+            roughness,
+            sheen: None,
+        }
+    }
+
+    // Cloth's sheen of `color`, with the spread of its fibres read from the luminance of
+    // `roughness`. The diffuse part reflects what the sheen does not.
+    pub fn with_sheen(mut self, color: &'a dyn Sampler2D, roughness: &'a dyn Sampler2D) -> Self {
+        self.sheen = Some((color, roughness));
+        self
+    }
+
+    // Directional albedo of the single scattering part for white albedo.
+    fn single_albedo(mu: Float, r: Float) -> Float {
+        let mu = mu.clamp(1.0e-4, 1.0);
+        let a = 1.0 / (1.0 + CONSTANT1 * r);
+        let b = r * a;
+        let si = (1.0 - mu * mu).sqrt();
+        let g = si * (mu.acos() - si * mu) + (2.0 / 3.0) * ((si / mu) * (1.0 - si * si * si) - si);
+        a + b / PI * g
+    }
+
+    fn diffuse(&self, rec: &HitRecord, wo: Vec3, wi: Vec3) -> Color {
+        let rho = self.albedo.sample(rec.uv);
+        let r = self.roughness.sample(rec.uv).luminance().clamp(0.0, 1.0);
+        let s = wi.dot(wo) - wi.z * wo.z;
+        let s_over_t = if s > 0.0 { s / wi.z.max(wo.z) } else { s };
+        let a = 1.0 / (1.0 + CONSTANT1 * r);
+        let single = rho * (a * (1.0 + r * s_over_t) / PI);
+
+        let average = a * (1.0 + CONSTANT2 * r);
+        let eps = 1.0e-7;
+        let lost = (1.0 - Self::single_albedo(wo.z, r)).max(eps)
+            * (1.0 - Self::single_albedo(wi.z, r)).max(eps)
+            / (1.0 - average).max(eps);
+        let multiple = |rho: Float| rho * rho * average / (1.0 - rho * (1.0 - average)) / PI * lost;
+        single + Color::new(multiple(rho.r()), multiple(rho.g()), multiple(rho.b()))
+    }
+
+    fn sheen_lobe(&self, rec: &HitRecord) -> Option<(Color, Charlie)> {
+        self.sheen.map(|(color, roughness)| {
+            let roughness = roughness.sample(rec.uv).luminance();
+            (
+                color.sample(rec.uv),
+                Charlie::new(TrowbridgeReitz::roughness_to_alpha(roughness)),
+            )
+        })
+    }
+
+    fn brightest(color: Color) -> Float {
+        color.r().max(color.g()).max(color.b()).clamp(0.0, 1.0)
+    }
+
+    // Cosine sampling of the diffuse part, mixed with the sheen's fibres in proportion to the
+    // light they reflect.
+    fn pdf(&self, r_in: &Ray, rec: &HitRecord) -> Box<dyn PDF> {
+        let (uvw, wo) = Self::outgoing(r_in, rec);
+        let cosine = Box::new(CosinePDF::new(ONB::new(&rec.normal)));
+        match self.sheen_lobe(rec) {
+            Some((color, charlie)) if wo.z > 0.0 => {
+                let sheen = Self::brightest(color) * charlie.albedo(wo.z);
+                Box::new(WeightedMixturePDF::new(vec![
+                    (1.0 - sheen, cosine as Box<dyn PDF>),
+                    (sheen, Box::new(CharliePDF::new(uvw, wo, charlie))),
+                ]))
+            }
+            _ => cosine,
+        }
+    }
+
+    fn outgoing(r_in: &Ray, rec: &HitRecord) -> (ONB, Vec3) {
+        let uvw = ONB::new(&rec.normal);
+        let wo = uvw.to_local(&-r_in.direction.normalize());
+        (uvw, wo)
+    }
+}
+
+impl<'a> Material for OrenNayar<'a> {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<MaterialHitResult> {
+        Some(MaterialHitResult::sampled(
+            Color::new(1.0, 1.0, 1.0),
+            rec,
+            self.pdf(r_in, rec),
+        ))
+    }
+
+    fn sampling_pdf(&self, r_in: &Ray, rec: &HitRecord, scattered_ray: &Ray) -> Option<Float> {
+        Some(self.pdf(r_in, rec).value(&scattered_ray.direction))
+    }
+
+    fn scattering_color(
+        &self,
+        r_in: &Ray,
+        rec: &HitRecord,
+        scattered_ray: &Ray,
+        color: Color,
+    ) -> Color {
+        let (uvw, wo) = Self::outgoing(r_in, rec);
+        let wi = uvw.to_local(&scattered_ray.direction.normalize());
+        if wo.z <= 0.0 || wi.z <= 0.0 {
+            return Color::new(0.0, 0.0, 0.0);
+        }
+        let diffuse = self.diffuse(rec, wo, wi) * wi.z;
+        let Some((sheen_color, charlie)) = self.sheen_lobe(rec) else {
+            return color * diffuse;
+        };
+        let wm = (wo + wi).normalize();
+        let sheen = sheen_color * (charlie.d(wm) * charlie.g(wo, wi) / (4.0 * wo.z));
+        let covered = Self::brightest(sheen_color) * charlie.albedo(wo.z);
+        color * (diffuse * (1.0 - covered) + sheen)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        materials::material::tests::{albedo, assert_albedo_within},
+        pdf::tests::outgoing,
+        texture::ColorTexture2D,
+    };
+
+    fn constant(value: Float) -> ColorTexture2D {
+        ColorTexture2D {
+            color: Color::new(value, value, value),
+        }
+    }
+
+    #[test]
+    fn white_furnace() {
+        let (white, half) = (constant(1.0), constant(0.5));
+        for wo in outgoing() {
+            for roughness in [&half, &white] {
+                let material = OrenNayar::new(&white, roughness);
+                assert_albedo_within(albedo(&material, wo), (0.99, 1.01));
+                let cloth = OrenNayar::new(&white, roughness).with_sheen(&white, &half);
+                assert_albedo_within(albedo(&cloth, wo), (0.99, 1.01));
+            }
+        }
+    }
+}
@@ -0,0 +1,531 @@
+use super::dielectric::Dielectric;
+use super::material::{Material, MaterialHitResult};
+use crate::color::Color;
+use crate::fresnel;
+use crate::hittable::HitRecord;
+use crate::microfacet::TrowbridgeReitz;
+use crate::onb::ONB;
+use crate::pdf::{CosinePDF, MicrofacetPDF, RoughDielectricPDF, WeightedMixturePDF, PDF};
+use crate::rand_vec3::{reflect, refract};
+use crate::ray::Ray;
+use crate::sampler::random;
+use crate::texture::Sampler2D;
+use crate::{Float, Vec3, PI};
+
+// Disney's principled BSDF as used by metallic/roughness assets: a Burley diffuse base with
+// sheen, a GGX specular lobe, a clearcoat and rough glass transmission. Every parameter but the
+// base colour is read from the luminance of its texture, and lies in [0, 1].
+pub struct Principled<'a> {
+    base_color: &'a dyn Sampler2D,
+    metallic: Option<&'a dyn Sampler2D>,
+    roughness: Option<&'a dyn Sampler2D>,
+    specular: Option<&'a dyn Sampler2D>,
+    specular_tint: Option<&'a dyn Sampler2D>,
+    sheen: Option<&'a dyn Sampler2D>,
+    sheen_tint: Option<&'a dyn Sampler2D>,
+    clearcoat: Option<&'a dyn Sampler2D>,
+    clearcoat_gloss: Option<&'a dyn Sampler2D>,
+    transmission: Option<&'a dyn Sampler2D>,
+    anisotropic: Option<&'a dyn Sampler2D>,
+    index_of_refraction: Float,
+}
+
+// The parameters at one point of the surface.
+struct Parameters {
+    base_color: Color,
+    metallic: Float,
+    roughness: Float,
+    specular: Float,
+    specular_tint: Float,
+    sheen: Float,
+    sheen_tint: Float,
+    clearcoat: Float,
+    clearcoat_gloss: Float,
+    transmission: Float,
+}
+
+// What each lobe contributes, given the parameters.
+struct Lobes {
+    diffuse: Float,
+    specular: Float,
+    glass: Float,
+    clearcoat: Float,
+    specular_distribution: TrowbridgeReitz,
+    glass_distribution: TrowbridgeReitz,
+    clearcoat_alpha: Float,
+    eta: Float,
+    // Whether the specular and glass lobes are mirror-like, with a single direction each.
+    smooth: bool,
+}
+
+// How often each lobe is sampled, relative to the others.
+struct Weights {
+    diffuse: Float,
+    specular: Float,
+    glass: Float,
+    clearcoat: Float,
+}
+
+impl<'a> Principled<'a> {
+    pub fn new(base_color: &'a dyn Sampler2D) -> Self {
+        Self {
+            base_color,
+            metallic: None,
+            roughness: None,
+            specular: None,
+            specular_tint: None,
+            sheen: None,
+            sheen_tint: None,
+            clearcoat: None,
+            clearcoat_gloss: None,
+            transmission: None,
+            anisotropic: None,
+            index_of_refraction: 1.5,
+        }
+    }
+
+    // Zero by default.
+    pub fn with_metallic(mut self, metallic: &'a dyn Sampler2D) -> Self {
+        self.metallic = Some(metallic);
+        self
+    }
+
+    // 0.5 by default.
+    pub fn with_roughness(mut self, roughness: &'a dyn Sampler2D) -> Self {
+        self.roughness = Some(roughness);
+        self
+    }
+
+    // Reflectance at normal incidence of non-metals, where 0.5 is 4%. 0.5 by default.
+    pub fn with_specular(mut self, specular: &'a dyn Sampler2D) -> Self {
+        self.specular = Some(specular);
+        self
+    }
+
+    // How far non-metallic reflections take on the base colour. Zero by default.
+    pub fn with_specular_tint(mut self, specular_tint: &'a dyn Sampler2D) -> Self {
+        self.specular_tint = Some(specular_tint);
+        self
+    }
+
+    // Extra reflection towards grazing angles, as on cloth. Zero by default.
+    pub fn with_sheen(mut self, sheen: &'a dyn Sampler2D) -> Self {
+        self.sheen = Some(sheen);
+        self
+    }
+
+    // 0.5 by default.
+    pub fn with_sheen_tint(mut self, sheen_tint: &'a dyn Sampler2D) -> Self {
+        self.sheen_tint = Some(sheen_tint);
+        self
+    }
+
+    // A second, clear specular layer such as car paint's varnish. Zero by default.
+    pub fn with_clearcoat(mut self, clearcoat: &'a dyn Sampler2D) -> Self {
+        self.clearcoat = Some(clearcoat);
+        self
+    }
+
+    // From a satin clearcoat at zero to a glossy one at one, the default.
+    pub fn with_clearcoat_gloss(mut self, clearcoat_gloss: &'a dyn Sampler2D) -> Self {
+        self.clearcoat_gloss = Some(clearcoat_gloss);
+        self
+    }
+
+    // Share of non-metallic light refracted through the surface rather than diffused. The object
+    // should be closed. Zero by default.
+    pub fn with_transmission(mut self, transmission: &'a dyn Sampler2D) -> Self {
+        self.transmission = Some(transmission);
+        self
+    }
+
+    // Stretches highlights along the tangent, which follows the first axis of `ONB::new`
+    // around the normal. Zero by default.
+    pub fn with_anisotropic(mut self, anisotropic: &'a dyn Sampler2D) -> Self {
+        self.anisotropic = Some(anisotropic);
+        self
+    }
+
+    // Of the inside when transmission refracts light. 1.5 by default.
+    pub fn with_index_of_refraction(mut self, index_of_refraction: Float) -> Self {
+        self.index_of_refraction = index_of_refraction;
+        self
+    }
+
+    fn parameters(&self, rec: &HitRecord) -> (Parameters, Float) {
+        let value = |texture: Option<&'a dyn Sampler2D>, default: Float| {
+            texture.map_or(default, |t| t.sample(rec.uv).luminance().clamp(0.0, 1.0))
+        };
+        let parameters = Parameters {
+            base_color: self.base_color.sample(rec.uv),
+            metallic: value(self.metallic, 0.0),
+            roughness: value(self.roughness, 0.5),
+            specular: value(self.specular, 0.5),
+            specular_tint: value(self.specular_tint, 0.0),
+            sheen: value(self.sheen, 0.0),
+            sheen_tint: value(self.sheen_tint, 0.5),
+            clearcoat: value(self.clearcoat, 0.0),
+            clearcoat_gloss: value(self.clearcoat_gloss, 1.0),
+            transmission: value(self.transmission, 0.0),
+        };
+        (parameters, value(self.anisotropic, 0.0))
+    }
+
+    fn lobes(&self, parameters: &Parameters, anisotropic: Float, rec: &HitRecord) -> Lobes {
+        let alpha = TrowbridgeReitz::roughness_to_alpha(parameters.roughness);
+        let aspect = (1.0 - 0.9 * anisotropic).sqrt();
+        let dielectric = 1.0 - parameters.metallic;
+        Lobes {
+            diffuse: dielectric * (1.0 - parameters.transmission),
+            specular: 1.0 - dielectric * parameters.transmission,
+            glass: dielectric * parameters.transmission,
+            clearcoat: 0.25 * parameters.clearcoat,
+            specular_distribution: TrowbridgeReitz::new(alpha / aspect, alpha * aspect),
+            glass_distribution: TrowbridgeReitz::new(alpha, alpha),
+            clearcoat_alpha: 0.1 + (0.001 - 0.1) * parameters.clearcoat_gloss,
+            eta: if rec.front_face {
+                self.index_of_refraction
+            } else {
+                1.0 / self.index_of_refraction
+            },
+            smooth: alpha < 1.0e-3,
+        }
+    }
+
+    // The base colour with its luminance taken out.
+    fn tint(base_color: Color) -> Color {
+        let luminance = base_color.luminance();
+        if luminance > 0.0 {
+            base_color / luminance
+        } else {
+            Color::new(1.0, 1.0, 1.0)
+        }
+    }
+
+    fn mix(a: Color, b: Color, t: Float) -> Color {
+        a * (1.0 - t) + b * t
+    }
+
+    // Reflectance at normal incidence of the specular lobe.
+    fn specular_color(parameters: &Parameters) -> Color {
+        let white = Color::new(1.0, 1.0, 1.0);
+        let tint = Self::tint(parameters.base_color);
+        let dielectric =
+            Self::mix(white, tint, parameters.specular_tint) * (0.08 * parameters.specular);
+        Self::mix(dielectric, parameters.base_color, parameters.metallic)
+    }
+
+    // Lobes are picked roughly in proportion to the light they reflect. The specular lobe keeps
+    // some weight even when black, so that a black metal still has a lobe to sample.
+    fn weights(parameters: &Parameters, lobes: &Lobes, wo: Vec3) -> Weights {
+        let specular = fresnel::schlick(Self::specular_color(parameters), wo.z)
+            .luminance()
+            .max(0.05);
+        Weights {
+            diffuse: lobes.diffuse * parameters.base_color.luminance().max(0.05),
+            specular: lobes.specular * specular,
+            glass: lobes.glass,
+            clearcoat: lobes.clearcoat * fresnel::schlick(Color::new(0.04, 0.04, 0.04), wo.z).r(),
+        }
+    }
+
+    // The lobes sampled by direction, which leaves out the specular and glass lobes of a smooth
+    // surface.
+    fn mixture(lobes: &Lobes, weights: &Weights, normal: Vec3, wo: Vec3) -> WeightedMixturePDF {
+        let mut components = vec![
+            (
+                weights.diffuse,
+                Box::new(CosinePDF::new(ONB::new(&normal))) as Box<dyn PDF>,
+            ),
+            (
+                weights.clearcoat,
+                Box::new(ClearcoatPDF {
+                    uvw: ONB::new(&normal),
+                    wo,
+                    alpha: lobes.clearcoat_alpha,
+                }),
+            ),
+        ];
+        if !lobes.smooth {
+            components.push((
+                weights.specular,
+                Box::new(MicrofacetPDF::new(
+                    ONB::new(&normal),
+                    wo,
+                    lobes.specular_distribution,
+                )),
+            ));
+            components.push((
+                weights.glass,
+                Box::new(RoughDielectricPDF::new(
+                    ONB::new(&normal),
+                    wo,
+                    lobes.eta,
+                    lobes.glass_distribution,
+                )),
+            ));
+        }
+        WeightedMixturePDF::new(components)
+    }
+
+    fn outgoing(r_in: &Ray, rec: &HitRecord) -> (ONB, Vec3) {
+        let uvw = ONB::new(&rec.normal);
+        let wo = uvw.to_local(&-r_in.direction.normalize());
+        (uvw, wo)
+    }
+}
+
+impl<'a> Material for Principled<'a> {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<MaterialHitResult> {
+        let (_, wo) = Self::outgoing(r_in, rec);
+        if wo.z <= 0.0 {
+            return None;
+        }
+        let (parameters, anisotropic) = self.parameters(rec);
+        let lobes = self.lobes(&parameters, anisotropic, rec);
+        let weights = Self::weights(&parameters, &lobes, wo);
+        if !lobes.smooth {
+            let pdf = Self::mixture(&lobes, &weights, rec.normal, wo);
+            return Some(MaterialHitResult::sampled(
+                Color::new(1.0, 1.0, 1.0),
+                rec,
+                Box::new(pdf),
+            ));
+        }
+
+        // Smooth specular and glass lobes reflect and refract in single directions, which are
+        // picked here with the same weights as the sampled lobes.
+        let total = weights.diffuse + weights.specular + weights.glass + weights.clearcoat;
+        let u = random() * total;
+        let unit_direction = r_in.direction.normalize();
+        let reflected = reflect(&unit_direction, &rec.normal);
+        if u < weights.specular {
+            let specular = fresnel::schlick(Self::specular_color(&parameters), wo.z);
+            return Some(MaterialHitResult {
+                color: specular * (lobes.specular * total / weights.specular),
+                ray: Ray::new(rec.p, reflected),
+                pdf: None,
+            });
+        }
+        if u < weights.specular + weights.glass {
+            let scale = lobes.glass * total / weights.glass;
+            let reflectance = fresnel::dielectric(wo.z, lobes.eta);
+            let (direction, color) = if random() < reflectance {
+                (reflected, Color::new(1.0, 1.0, 1.0))
+            } else {
+                let refracted = refract(&unit_direction, &rec.normal, 1.0 / lobes.eta);
+                (refracted, parameters.base_color)
+            };
+            return Some(MaterialHitResult {
+                color: color * scale,
+                ray: Ray::new(rec.p, direction),
+                pdf: None,
+            });
+        }
+        let sampled = (weights.diffuse + weights.clearcoat) / total;
+        let pdf = Self::mixture(&lobes, &weights, rec.normal, wo);
+        Some(MaterialHitResult::sampled(
+            Color::new(1.0, 1.0, 1.0) / sampled,
+            rec,
+            Box::new(pdf),
+        ))
+    }
+
+    // Smooth lobes are picked at random by `scatter`, so the density of the others is scaled by
+    // the probability of sampling them at all.
+    fn sampling_pdf(&self, r_in: &Ray, rec: &HitRecord, scattered_ray: &Ray) -> Option<Float> {
+        let (_, wo) = Self::outgoing(r_in, rec);
+        if wo.z <= 0.0 {
+            return None;
+        }
+        let (parameters, anisotropic) = self.parameters(rec);
+        let lobes = self.lobes(&parameters, anisotropic, rec);
+        let weights = Self::weights(&parameters, &lobes, wo);
+        let sampled = if lobes.smooth {
+            let total = weights.diffuse + weights.specular + weights.glass + weights.clearcoat;
+            (weights.diffuse + weights.clearcoat) / total
+        } else {
+            1.0
+        };
+        if sampled <= 0.0 {
+            return None;
+        }
+        let pdf = Self::mixture(&lobes, &weights, rec.normal, wo);
+        Some(sampled * pdf.value(&scattered_ray.direction))
+    }
+
+    fn scattering_color(
+        &self,
+        r_in: &Ray,
+        rec: &HitRecord,
+        scattered_ray: &Ray,
+        color: Color,
+    ) -> Color {
+        let black = Color::new(0.0, 0.0, 0.0);
+        let (uvw, wo) = Self::outgoing(r_in, rec);
+        let wi = uvw.to_local(&scattered_ray.direction.normalize());
+        if wo.z <= 0.0 || wi.z == 0.0 {
+            return black;
+        }
+        let (parameters, anisotropic) = self.parameters(rec);
+        let lobes = self.lobes(&parameters, anisotropic, rec);
+
+        // Smooth lobes only reflect and refract in the directions `scatter` picks.
+        let glass = if lobes.smooth {
+            0.0
+        } else {
+            Dielectric::rough_scattering(wo, wi, lobes.eta, &lobes.glass_distribution)
+        };
+        if wi.z < 0.0 {
+            return color * parameters.base_color * (lobes.glass * glass);
+        }
+
+        let wm = (wo + wi).normalize();
+        let cos_d = wi.dot(wm);
+        let schlick_weight = (1.0 - cos_d).clamp(0.0, 1.0).powi(5);
+
+        // Burley's diffuse, brighter at grazing angles on rough surfaces, and sheen.
+        let fd90 = 0.5 + 2.0 * parameters.roughness * cos_d * cos_d;
+        let retro = |cos: Float| 1.0 + (fd90 - 1.0) * (1.0 - cos).powi(5);
+        let diffuse = parameters.base_color * (retro(wo.z) * retro(wi.z) / PI);
+        let sheen_color = Self::mix(
+            Color::new(1.0, 1.0, 1.0),
+            Self::tint(parameters.base_color),
+            parameters.sheen_tint,
+        );
+        let sheen = sheen_color * (parameters.sheen * schlick_weight);
+
+        let distribution = lobes.specular_distribution;
+        let specular = if lobes.smooth {
+            black
+        } else {
+            fresnel::schlick(Self::specular_color(&parameters), cos_d)
+                * (distribution.d(wm) * distribution.g(wo, wi) / (4.0 * wo.z * wi.z))
+        };
+
+        let clearcoat_distribution = TrowbridgeReitz::new(0.25, 0.25);
+        let clearcoat = (0.04 + 0.96 * schlick_weight)
+            * ClearcoatPDF::d(wm.z, lobes.clearcoat_alpha)
+            * clearcoat_distribution.g1(wo)
+            * clearcoat_distribution.g1(wi)
+            / (4.0 * wo.z * wi.z);
+
+        let white = Color::new(1.0, 1.0, 1.0);
+        color
+            * ((diffuse + sheen) * (lobes.diffuse * wi.z)
+                + specular * (lobes.specular * wi.z)
+                + white * (lobes.glass * glass + lobes.clearcoat * clearcoat * wi.z))
+    }
+}
+
+// Reflection off clearcoat microfacets of the GTR1 distribution, sampled about the normal.
+struct ClearcoatPDF {
+    uvw: ONB,
+    wo: Vec3,
+    alpha: Float,
+}
+
+impl ClearcoatPDF {
+    fn d(cos_theta: Float, alpha: Float) -> Float {
+        let a2 = alpha * alpha;
+        let t = 1.0 + (a2 - 1.0) * cos_theta * cos_theta;
+        (a2 - 1.0) / (PI * a2.ln() * t)
+    }
+}
+
+impl PDF for ClearcoatPDF {
+    fn value(&self, direction: &Vec3) -> Float {
+        let wi = self.uvw.to_local(&direction.normalize());
+        if wi.z <= 0.0 {
+            return 0.0;
+        }
+        let wm = (self.wo + wi).normalize();
+        Self::d(wm.z, self.alpha) * wm.z / (4.0 * self.wo.dot(wm))
+    }
+
+    fn generate(&self) -> Option<Vec3> {
+        let a2 = self.alpha * self.alpha;
+        let cos_theta = ((1.0 - a2.powf(1.0 - random())) / (1.0 - a2))
+            .max(0.0)
+            .sqrt();
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        let phi = 2.0 * PI * random();
+        let wm = Vec3::new(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta);
+        let wi = reflect(&-self.wo, &wm);
+        if wi.z <= 0.0 {
+            return None;
+        }
+        Some(self.uvw.transform(&wi))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        materials::material::tests::{albedo, assert_albedo_within},
+        pdf::tests::{assert_samples_match, frame, integrate, outgoing},
+        texture::ColorTexture2D,
+    };
+
+    fn constant(value: Float) -> ColorTexture2D {
+        ColorTexture2D {
+            color: Color::new(value, value, value),
+        }
+    }
+
+    #[test]
+    fn clearcoat_samples_match_their_density() {
+        for wo in outgoing() {
+            for alpha in [0.25, 0.5] {
+                let pdf = ClearcoatPDF {
+                    uvw: frame(),
+                    wo,
+                    alpha,
+                };
+                let total = integrate(|direction| pdf.value(&direction));
+                assert!(total <= 1.0 + 1.0e-2, "The density integrates to {}", total);
+                assert_samples_match(|| pdf.generate(), |direction| pdf.value(&direction));
+            }
+        }
+    }
+
+    #[test]
+    fn white_furnace() {
+        let (zero, half, one) = (constant(0.0), constant(0.5), constant(1.0));
+        let conserving = [
+            Principled::new(&one).with_metallic(&one),
+            Principled::new(&one)
+                .with_metallic(&one)
+                .with_roughness(&zero),
+            Principled::new(&one)
+                .with_anisotropic(&one)
+                .with_metallic(&half),
+            Principled::new(&one).with_transmission(&one),
+            Principled::new(&one)
+                .with_transmission(&one)
+                .with_roughness(&zero),
+        ];
+        for material in &conserving {
+            for wo in outgoing() {
+                assert_albedo_within(albedo(material, wo), (0.5, 1.01));
+            }
+        }
+
+        // Burley's diffuse brightens towards grazing angles, and the specular lobe and clearcoat
+        // are added on top of it, so diffuse surfaces reflect somewhat more than they receive.
+        let diffuse = [
+            Principled::new(&one),
+            Principled::new(&one).with_roughness(&zero),
+            Principled::new(&one).with_roughness(&one),
+            Principled::new(&one)
+                .with_clearcoat(&one)
+                .with_clearcoat_gloss(&half),
+        ];
+        for material in &diffuse {
+            for wo in outgoing() {
+                assert_albedo_within(albedo(material, wo), (0.9, 1.4));
+            }
+        }
+    }
+}
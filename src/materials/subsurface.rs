@@ -0,0 +1,160 @@
+use std::fmt::{Debug, Write};
+
+use super::dielectric::Dielectric;
+use super::material::{Material, MaterialHitResult};
+use crate::aabb::AABB;
+use crate::color::Color;
+use crate::hittable::{HitRecord, Hittable};
+use crate::interval::Interval;
+use crate::pdf::{HenyeyGreensteinPDF, PDF};
+use crate::ray::Ray;
+use crate::sampler::random;
+use crate::Float;
+
+// Collisions after which a walk still inside the object is taken to be absorbed.
+const MAX_STEPS: usize = 4096;
+
+// Translucent object such as skin, wax, marble or milk, filling the inside of `boundary` with a
+// scattering material. Light refracting into it takes a volumetric random walk against the
+// boundary alone until it refracts out again, so the boundary must be closed and other objects
+// placed inside it are not seen by the walk.
+pub struct Subsurface<'a> {
+    boundary: &'a dyn Hittable,
+    walk: Walk<'a>,
+    name: String,
+}
+
+impl<'a> Subsurface<'a> {
+    // `albedo` is the share of each colour channel scattered rather than absorbed at every
+    // collision and `mean_free_path` the average distance travelled between collisions, in
+    // scene units. `g` is the Henyey-Greenstein asymmetry of scattering inside.
+    pub fn new(
+        boundary: &'a dyn Hittable,
+        albedo: Color,
+        mean_free_path: Color,
+        index_of_refraction: Float,
+        g: Float,
+    ) -> Self {
+        let albedo = [albedo.r(), albedo.g(), albedo.b()];
+        let mean_free_path = [mean_free_path.r(), mean_free_path.g(), mean_free_path.b()];
+        Self {
+            boundary,
+            walk: Walk {
+                boundary,
+                albedo: albedo.map(|a| a.clamp(0.0, 1.0)),
+                sigma_t: mean_free_path.map(|d| 1.0 / d.max(1.0e-6)),
+                index_of_refraction,
+                g,
+            },
+            name: "Subsurface".into(),
+        }
+    }
+}
+
+impl<'a> Hittable for Subsurface<'a> {
+    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
+        let rec = self.boundary.hit(r, ray_t)?;
+        Some(HitRecord {
+            material: &self.walk,
+            ..rec
+        })
+    }
+
+    fn bounding_box(&self) -> AABB {
+        self.boundary.bounding_box()
+    }
+
+    fn get_name(&self) -> &String {
+        &self.name
+    }
+}
+
+impl<'a> Debug for Subsurface<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(" Subsurface ")?;
+        f.write_char('\n')?;
+        Ok(())
+    }
+}
+
+// The surface of a subsurface object, which walks every ray refracted in until it comes out.
+struct Walk<'a> {
+    boundary: &'a dyn Hittable,
+    albedo: [Float; 3],
+    sigma_t: [Float; 3],
+    index_of_refraction: Float,
+    g: Float,
+}
+
+impl<'a> Material for Walk<'a> {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<MaterialHitResult> {
+        let unit_direction = r_in.direction.normalize();
+        // Rays starting inside, such as from a camera there, walk on from where they started.
+        let mut ray = if rec.front_face {
+            let direction =
+                Dielectric::boundary_direction(unit_direction, rec, self.index_of_refraction);
+            if direction.dot(rec.normal) >= 0.0 {
+                return Some(MaterialHitResult {
+                    color: Color::new(1.0, 1.0, 1.0),
+                    ray: Ray::new(rec.p, direction),
+                    pdf: None,
+                });
+            }
+            Ray::new(rec.p, direction)
+        } else {
+            Ray::new(r_in.origin, unit_direction)
+        };
+
+        // Distances are sampled for a single channel per walk, and each channel weighted by the
+        // probability of the walk had it been chosen over that of the walk with any channel.
+        // Weighing whole walks rather than single steps keeps the weights bounded.
+        let channel = (random() * 3.0) as usize % 3;
+        let mut probability: [Float; 3] = [1.0; 3];
+        let mut albedo: [Float; 3] = [1.0; 3];
+        for _ in 0..MAX_STEPS {
+            let exit = self.boundary.hit(
+                &ray,
+                Interval {
+                    min: 0.001,
+                    max: Float::MAX,
+                },
+            )?;
+            let collision = -(1.0 - random()).ln() / self.sigma_t[channel];
+            let scattered = collision < exit.t;
+            let travelled = if scattered { collision } else { exit.t };
+            for i in 0..3 {
+                probability[i] *= (-self.sigma_t[i] * travelled).exp();
+                if scattered {
+                    probability[i] *= self.sigma_t[i];
+                    albedo[i] *= self.albedo[i];
+                }
+            }
+            let average = (probability[0] + probability[1] + probability[2]) / 3.0;
+            if average <= 0.0 {
+                return None;
+            }
+            probability = probability.map(|p| p / average);
+
+            if scattered {
+                let phase = HenyeyGreensteinPDF::new(&ray.direction, self.g);
+                ray = Ray::new(ray.at(collision), phase.generate()?);
+                continue;
+            }
+            let direction =
+                Dielectric::boundary_direction(ray.direction, &exit, self.index_of_refraction);
+            if direction.dot(exit.normal) < 0.0 {
+                return Some(MaterialHitResult {
+                    color: Color::new(
+                        albedo[0] * probability[0],
+                        albedo[1] * probability[1],
+                        albedo[2] * probability[2],
+                    ),
+                    ray: Ray::new(exit.p, direction),
+                    pdf: None,
+                });
+            }
+            ray = Ray::new(exit.p, direction);
+        }
+        None
+    }
+}
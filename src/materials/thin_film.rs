@@ -0,0 +1,54 @@
+use crate::{color::Color, fresnel, texture::Sampler2D, Float, Vec2};
+
+// A transparent film a few hundred nanometres thick on the surface of a material, as on a soap
+// bubble, an oil slick or a coated lens. Light reflected off its top and bottom interferes, so
+// its colour shifts with the thickness and the viewing angle.
+pub struct ThinFilm<'a> {
+    // Thickness in nanometres, read from the luminance.
+    thickness: &'a dyn Sampler2D,
+    index_of_refraction: Float,
+}
+
+impl<'a> ThinFilm<'a> {
+    pub fn new(thickness: &'a dyn Sampler2D, index_of_refraction: Float) -> Self {
+        Self {
+            thickness,
+            index_of_refraction,
+        }
+    }
+
+    // Reflectance of the film at `uv` over a substrate of complex index of refraction `eta + i k`,
+    // seen from a medium of index `outside`, at wavelength `lambda` when rendering spectrally.
+    pub(crate) fn reflectance(
+        &self,
+        uv: Vec2,
+        cos_theta: Float,
+        outside: Float,
+        eta: Color,
+        k: Color,
+        lambda: Option<Float>,
+    ) -> Color {
+        let thickness = self.thickness.sample(uv).luminance();
+        // Rendering spectrally, the interference at the wavelength is exact.
+        if let Some(lambda) = lambda {
+            let r = fresnel::thin_film(
+                cos_theta,
+                outside,
+                self.index_of_refraction,
+                thickness,
+                fresnel::channel_at(eta, lambda),
+                fresnel::channel_at(k, lambda),
+                lambda,
+            );
+            return Color::new(r, r, r);
+        }
+        fresnel::thin_film_rgb(
+            cos_theta,
+            outside,
+            self.index_of_refraction,
+            thickness,
+            eta,
+            k,
+        )
+    }
+}
@@ -0,0 +1,305 @@
+use std::fmt::{Debug, Write};
+
+use crate::{
+    aabb::AABB,
+    color::Color,
+    hittable::{HitRecord, Hittable},
+    interval::Interval,
+    materials::{
+        henyey_greenstein::HenyeyGreenstein,
+        material::{Material, MaterialHitResult},
+    },
+    pdf::{HenyeyGreensteinPDF, PDF},
+    ray::Ray,
+    sampler::random,
+    texture::Sampler3D,
+    Float, Mat4, Vec2, Vec3,
+};
+
+// A participating medium of constant density. Rays are scattered at a free-flight distance
+// sampled from its extinction coefficient, so transmittance is accounted for by rays, shadow
+// rays included, passing through it unhindered with the right probability.
+pub struct ConstantMedium<'a> {
+    boundary: Option<&'a dyn Hittable>,
+    max_distance: Float,
+    sigma_t: Float,
+    phase: HenyeyGreenstein,
+    name: String,
+}
+
+impl<'a> ConstantMedium<'a> {
+    // Fills the inside of `boundary`, which must be closed. Of the light interacting
+    // with the medium a share of `sigma_s / (sigma_a + sigma_s)` is scattered, tinted by `color`.
+    pub fn new(
+        boundary: &'a dyn Hittable,
+        sigma_a: Float,
+        sigma_s: Float,
+        color: Color,
+        g: Float,
+    ) -> Self {
+        Self::build(Some(boundary), Float::MAX, sigma_a, sigma_s, color, g)
+    }
+
+    // Atmospheric fog filling all of space up to `max_distance` from the start of each ray, so
+    // that the sky and distant lights still reach the scene.
+    pub fn fog(
+        sigma_a: Float,
+        sigma_s: Float,
+        color: Color,
+        g: Float,
+        max_distance: Float,
+    ) -> Self {
+        Self::build(None, max_distance, sigma_a, sigma_s, color, g)
+    }
+
+    fn build(
+        boundary: Option<&'a dyn Hittable>,
+        max_distance: Float,
+        sigma_a: Float,
+        sigma_s: Float,
+        color: Color,
+        g: Float,
+    ) -> Self {
+        let sigma_t = sigma_a.max(0.0) + sigma_s.max(0.0);
+        let albedo = if sigma_t > 0.0 {
+            color * (sigma_s.max(0.0) / sigma_t)
+        } else {
+            Color::new(0.0, 0.0, 0.0)
+        };
+        Self {
+            boundary,
+            max_distance,
+            sigma_t,
+            phase: HenyeyGreenstein::new(albedo, g),
+            name: "ConstantMedium".into(),
+        }
+    }
+
+    // The next stretch of `r` inside the boundary that starts after `after`. Rays are taken to
+    // start outside the boundary far behind their origin, so that entries and exits alternate.
+    fn next_inside(boundary: &dyn Hittable, r: &Ray, after: Float) -> Option<(Float, Float)> {
+        let entry = boundary.hit(r, Interval::new(after, Float::MAX))?;
+        let exit = boundary.hit(r, Interval::new(entry.t + 0.0001, Float::MAX))?;
+        Some((entry.t, exit.t))
+    }
+
+    fn collision(&self, r: &Ray, t: Float) -> HitRecord<'_> {
+        HitRecord::new(
+            r.at(t),
+            t,
+            -r.direction.normalize(),
+            r,
+            &self.phase,
+            Vec2::ZERO,
+        )
+    }
+}
+
+impl<'a> Hittable for ConstantMedium<'a> {
+    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
+        if self.sigma_t <= 0.0 {
+            return None;
+        }
+        let length = r.direction.length();
+        // The free-flight distance is used up by each stretch inside the boundary in turn, so a
+        // ray leaving a concave boundary can still collide after entering it again.
+        let mut remaining = -(1.0 - random()).ln() / self.sigma_t;
+
+        let Some(boundary) = self.boundary else {
+            let t = ray_t.min + remaining / length;
+            let cutoff = ray_t.min + self.max_distance / length;
+            return (t < ray_t.max.min(cutoff)).then(|| self.collision(r, t));
+        };
+        let mut after = Interval::universe().min;
+        while let Some((entry, exit)) = Self::next_inside(boundary, r, after) {
+            let (start, end) = (entry.max(ray_t.min).max(0.0), exit.min(ray_t.max));
+            if start < end {
+                let distance_inside = (end - start) * length;
+                if remaining <= distance_inside {
+                    return Some(self.collision(r, start + remaining / length));
+                }
+                remaining -= distance_inside;
+            }
+            if exit >= ray_t.max {
+                return None;
+            }
+            after = exit + 0.0001;
+        }
+        None
+    }
+
+    fn bounding_box(&self) -> AABB {
+        self.boundary.map_or(
+            AABB::from_extrema(Vec3::splat(-Float::MAX), Vec3::splat(Float::MAX)),
+            |boundary| boundary.bounding_box(),
+        )
+    }
+
+    fn get_name(&self) -> &String {
+        &self.name
+    }
+}
+
+impl<'a> Debug for ConstantMedium<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(" ConstantMedium ")?;
+        f.write_char('\n')?;
+        Ok(())
+    }
+}
+
+// Phase function of a medium whose colour varies through its grid.
+struct GridPhase<'a> {
+    color: &'a dyn Sampler3D,
+    emission: Option<&'a dyn Sampler3D>,
+    world_to_grid: Mat4,
+    albedo: Float,
+    g: Float,
+}
+
+impl<'a> GridPhase<'a> {
+    fn to_grid(&self, p: Vec3) -> Vec3 {
+        (self.world_to_grid * p.extend(1.0)).truncate()
+    }
+}
+
+impl<'a> Material for GridPhase<'a> {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<MaterialHitResult> {
+        let pdf = Box::new(HenyeyGreensteinPDF::new(&r_in.direction, self.g));
+        Some(MaterialHitResult::sampled(
+            self.color.sample(self.to_grid(rec.p)) * self.albedo,
+            rec,
+            pdf,
+        ))
+    }
+
+    fn scattering_pdf(&self, r_in: &Ray, _rec: &HitRecord, scattered_ray: &Ray) -> Float {
+        HenyeyGreensteinPDF::new(&r_in.direction, self.g).value(&scattered_ray.direction)
+    }
+
+    // Collisions are absorptions in the proportion that the medium emits.
+    fn emit_color(&self, _r: &Ray, rec: &HitRecord) -> Color {
+        self.emission.map_or(Color::new(0.0, 0.0, 0.0), |emission| {
+            emission.sample(self.to_grid(rec.p)) * (1.0 - self.albedo)
+        })
+    }
+}
+
+// A medium whose density is looked up in a grid, such as a simulated cloud, filling the unit
+// cube placed in the world by `transform`. Collisions are found by delta tracking against the
+// maximum density, which keeps both scattering and transmittance unbiased.
+pub struct HeterogeneousMedium<'a> {
+    density: &'a dyn Sampler3D,
+    // Extinction at the highest density the grid reaches.
+    sigma_max: Float,
+    sigma_t: Float,
+    world_to_grid: Mat4,
+    phase: GridPhase<'a>,
+    aabb: AABB,
+    name: String,
+}
+
+impl<'a> HeterogeneousMedium<'a> {
+    // The luminance of `density` scales `sigma_a` and `sigma_s` and must not exceed `max_density`,
+    // for instance `VoxelGrid::max_value`. `color` tints the scattered light.
+    pub fn new(
+        density: &'a dyn Sampler3D,
+        max_density: Float,
+        color: &'a dyn Sampler3D,
+        sigma_a: Float,
+        sigma_s: Float,
+        g: Float,
+        transform: Mat4,
+    ) -> Self {
+        let sigma_t = sigma_a.max(0.0) + sigma_s.max(0.0);
+        let world_to_grid = transform.inverse();
+        Self {
+            density,
+            sigma_max: max_density.max(0.0) * sigma_t,
+            sigma_t,
+            world_to_grid,
+            phase: GridPhase {
+                color,
+                emission: None,
+                world_to_grid,
+                albedo: if sigma_t > 0.0 {
+                    sigma_s.max(0.0) / sigma_t
+                } else {
+                    0.0
+                },
+                g,
+            },
+            aabb: AABB::from_extrema(Vec3::ZERO, Vec3::ONE).transform(transform),
+            name: "HeterogeneousMedium".into(),
+        }
+    }
+
+    // Makes the medium glow with the radiance `emission` gives in grid space, for instance a
+    // `BlackbodyRamp` over a temperature grid. Only absorbing media emit, as absorption and
+    // emission go together.
+    pub fn with_emission(mut self, emission: &'a dyn Sampler3D) -> Self {
+        self.phase.emission = Some(emission);
+        self
+    }
+
+    // Where a ray in grid space enters and leaves the unit cube.
+    fn unit_cube_extent(r: &Ray) -> (Float, Float) {
+        let inverse = r.direction.recip();
+        let t0 = (Vec3::ZERO - r.origin) * inverse;
+        let t1 = (Vec3::ONE - r.origin) * inverse;
+        (t0.min(t1).max_element(), t0.max(t1).min_element())
+    }
+}
+
+impl<'a> Hittable for HeterogeneousMedium<'a> {
+    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
+        if self.sigma_max <= 0.0 {
+            return None;
+        }
+        // Ray parameters are shared between world and grid space, the transform being affine.
+        let local = Ray::new(
+            (self.world_to_grid * r.origin.extend(1.0)).truncate(),
+            (self.world_to_grid * r.direction.extend(0.0)).truncate(),
+        );
+        let (enter, exit) = Self::unit_cube_extent(&local);
+        let (mut t, exit) = (enter.max(ray_t.min), exit.min(ray_t.max));
+        if t >= exit {
+            return None;
+        }
+
+        let length = r.direction.length();
+        loop {
+            t -= (1.0 - random()).ln() / (self.sigma_max * length);
+            if t >= exit {
+                return None;
+            }
+            let sigma = self.density.sample(local.at(t)).luminance() * self.sigma_t;
+            if random() * self.sigma_max < sigma {
+                return Some(HitRecord::new(
+                    r.at(t),
+                    t,
+                    -r.direction / length,
+                    r,
+                    &self.phase,
+                    Vec2::ZERO,
+                ));
+            }
+        }
+    }
+
+    fn bounding_box(&self) -> AABB {
+        self.aabb
+    }
+
+    fn get_name(&self) -> &String {
+        &self.name
+    }
+}
+
+impl<'a> Debug for HeterogeneousMedium<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(" HeterogeneousMedium ")?;
+        f.write_char('\n')?;
+        Ok(())
+    }
+}
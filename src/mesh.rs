@@ -2,9 +2,14 @@ use std::fmt::Debug;
 use std::fmt::Write;
 
 use crate::aabb::AABB;
+use crate::alias_table::AliasTable;
 use crate::hittable::HitRecord;
 use crate::hittable::Hittable;
+use crate::hittable::SurfaceSample;
+use crate::interval::Interval;
 use crate::materials::material::Material;
+use crate::ray::Ray;
+use crate::sampler::random;
 use crate::triangle::Triangle;
 use crate::vertex::Vertex;
 use crate::Float;
@@ -12,6 +17,11 @@ use crate::{Mat4, Vec2, Vec3};
 use russimp::scene::{PostProcess, Scene};
 use russimp::Vector3D;
 
+// Triangles subtending less than this solid angle are sampled by area, where spherical
+// sampling loses precision; so are ones that cover nearly the whole sphere.
+const MIN_SPHERICAL_SOLID_ANGLE: Float = 3.0e-4;
+const MAX_SPHERICAL_SOLID_ANGLE: Float = 6.22;
+
 pub struct MeshOptions {
     triangles: Vec<Triangle>,
     aabb: AABB,
@@ -93,6 +103,10 @@ pub struct Mesh<'a> {
     transform: Mat4,
     inverse_transform: Mat4,
     aabb: AABB,
+    // The triangles placed in the world, with an area-proportional table to pick them for sampling.
+    world_triangles: Vec<Triangle>,
+    triangle_table: AliasTable,
+    area: Float,
 }
 
 impl<'a> Mesh<'a> {
@@ -103,15 +117,41 @@ impl<'a> Mesh<'a> {
         transform: Mat4,
     ) -> Self {
         let aabb = options.aabb.transform(transform);
+        let inverse_transform = transform.inverse();
+        let to_world = |v: &Vertex| Vertex {
+            pos: (transform * v.pos.extend(1.0)).truncate(),
+            normal: (inverse_transform.transpose() * v.normal.extend(0.0))
+                .truncate()
+                .normalize(),
+            uv: v.uv,
+        };
+        let world_triangles: Vec<Triangle> = options
+            .triangles
+            .iter()
+            .map(|t| Triangle {
+                v0: to_world(&t.v0),
+                v1: to_world(&t.v1),
+                v2: to_world(&t.v2),
+            })
+            .collect();
+        let areas: Vec<Float> = world_triangles.iter().map(|t| t.area()).collect();
+
         Self {
             options,
             material,
             name,
             transform,
-            inverse_transform: transform.inverse(),
+            inverse_transform,
             aabb,
+            triangle_table: AliasTable::new(&areas),
+            area: areas.iter().sum(),
+            world_triangles,
         }
     }
+
+    fn samples_solid_angle(solid_angle: Float) -> bool {
+        (MIN_SPHERICAL_SOLID_ANGLE..=MAX_SPHERICAL_SOLID_ANGLE).contains(&solid_angle)
+    }
 }
 
 impl<'a> Hittable for Mesh<'a> {
@@ -148,6 +188,74 @@ impl<'a> Hittable for Mesh<'a> {
     fn get_name(&self) -> &String {
         &self.name
     }
+
+    fn emitters(&self) -> Vec<&dyn Hittable> {
+        if self.material.is_emissive() {
+            vec![self]
+        } else {
+            vec![]
+        }
+    }
+
+    fn pdf_value(&self, origin: &Vec3, direction: &Vec3) -> Float {
+        let r = Ray::new(*origin, *direction);
+        self.world_triangles
+            .iter()
+            .enumerate()
+            .filter_map(|(index, triangle)| {
+                let t = triangle.intersect(&r, Interval::new(0.0001, Float::MAX))?;
+                let probability = self.triangle_table.probability(index);
+                let solid_angle = triangle.solid_angle(*origin);
+                if Self::samples_solid_angle(solid_angle) {
+                    return Some(probability / solid_angle);
+                }
+                let distance_squared = t * t * direction.length_squared();
+                let cosine = direction.dot(triangle.geometric_normal()).abs() / direction.length();
+                Some(probability * distance_squared / (cosine * triangle.area()))
+            })
+            .sum()
+    }
+
+    fn random_vector_to_surface(&self, origin: &Vec3) -> Vec3 {
+        if self.triangle_table.is_empty() {
+            return Vec3::X;
+        }
+        let triangle = &self.world_triangles[self.triangle_table.sample(random())];
+        if Self::samples_solid_angle(triangle.solid_angle(*origin)) {
+            if let Some(direction) = triangle.sample_solid_angle(*origin) {
+                let t = triangle
+                    .intersect(&Ray::new(*origin, direction), Interval::universe())
+                    .unwrap_or(1.0);
+                return direction * t;
+            }
+        }
+        triangle.sample_area().p - *origin
+    }
+
+    fn sample_surface(&self) -> Option<SurfaceSample> {
+        if self.triangle_table.is_empty() {
+            return None;
+        }
+        let sample = self.world_triangles[self.triangle_table.sample(random())].sample_area();
+        Some(SurfaceSample {
+            pdf: 1.0 / self.area,
+            ..sample
+        })
+    }
+
+    fn surface_pdf_value(&self, origin: &Vec3, direction: &Vec3) -> Float {
+        let r = Ray::new(*origin, *direction);
+        let hit = self.world_triangles.iter().any(|triangle| {
+            triangle
+                .intersect(&r, Interval::new(0.0001, Float::MAX))
+                .is_some()
+        });
+        if hit {
+            1.0 / self.area
+        } else {
+            0.0
+        }
+    }
 }
 
 impl<'a> Debug for Mesh<'a> {
@@ -0,0 +1,195 @@
+use std::sync::OnceLock;
+
+use crate::{Float, Vec2, Vec3, PI};
+
+// The GGX or Trowbridge-Reitz distribution of microfacet normals, in the local frame of the
+// surface with z along its normal. `alpha_x` and `alpha_y` are the roughness along the tangent
+// and bitangent, which differ for brushed surfaces.
+#[derive(Clone, Copy)]
+pub struct TrowbridgeReitz {
+    alpha_x: Float,
+    alpha_y: Float,
+}
+
+impl TrowbridgeReitz {
+    pub fn new(alpha_x: Float, alpha_y: Float) -> Self {
+        Self {
+            alpha_x: alpha_x.max(1.0e-4),
+            alpha_y: alpha_y.max(1.0e-4),
+        }
+    }
+
+    // Perceptual roughness in [0, 1] to alpha, which makes roughness look roughly linear.
+    pub fn roughness_to_alpha(roughness: Float) -> Float {
+        let roughness = roughness.clamp(0.0, 1.0);
+        roughness * roughness
+    }
+
+    // So close to a mirror that it is better treated as one.
+    pub fn is_smooth(&self) -> bool {
+        self.alpha_x.max(self.alpha_y) < 1.0e-3
+    }
+
+    pub fn d(&self, wm: Vec3) -> Float {
+        if wm.z <= 0.0 {
+            return 0.0;
+        }
+        let x = wm.x / self.alpha_x;
+        let y = wm.y / self.alpha_y;
+        let denom = x * x + y * y + wm.z * wm.z;
+        1.0 / (PI * self.alpha_x * self.alpha_y * denom * denom)
+    }
+
+    fn lambda(&self, w: Vec3) -> Float {
+        if w.z == 0.0 {
+            return Float::MAX;
+        }
+        let x = self.alpha_x * w.x;
+        let y = self.alpha_y * w.y;
+        ((1.0 + (x * x + y * y) / (w.z * w.z)).sqrt() - 1.0) / 2.0
+    }
+
+    // Smith masking of microfacets seen from `w`.
+    pub fn g1(&self, w: Vec3) -> Float {
+        1.0 / (1.0 + self.lambda(w))
+    }
+
+    // Height correlated Smith masking and shadowing.
+    pub fn g(&self, wo: Vec3, wi: Vec3) -> Float {
+        1.0 / (1.0 + self.lambda(wo) + self.lambda(wi))
+    }
+
+    // Density of the microfacet normals visible from `w`.
+    pub fn d_visible(&self, w: Vec3, wm: Vec3) -> Float {
+        if w.z <= 0.0 {
+            return 0.0;
+        }
+        self.g1(w) / w.z * self.d(wm) * w.dot(wm).max(0.0)
+    }
+
+    // Heitz's sampling of the normals visible from `w`, which must be above the surface.
+    pub fn sample_visible_normal(&self, w: Vec3, u: Vec2) -> Vec3 {
+        let wh = Vec3::new(self.alpha_x * w.x, self.alpha_y * w.y, w.z).normalize();
+        let length_squared = wh.x * wh.x + wh.y * wh.y;
+        let t1 = if length_squared > 0.0 {
+            Vec3::new(-wh.y, wh.x, 0.0) / length_squared.sqrt()
+        } else {
+            Vec3::X
+        };
+        let t2 = wh.cross(t1);
+
+        let r = u.x.sqrt();
+        let phi = 2.0 * PI * u.y;
+        let p1 = r * phi.cos();
+        let s = 0.5 * (1.0 + wh.z);
+        let p2 = (1.0 - s) * (1.0 - p1 * p1).max(0.0).sqrt() + s * r * phi.sin();
+        let nh = p1 * t1 + p2 * t2 + (1.0 - p1 * p1 - p2 * p2).max(0.0).sqrt() * wh;
+        Vec3::new(self.alpha_x * nh.x, self.alpha_y * nh.y, nh.z.max(1.0e-6)).normalize()
+    }
+}
+
+// Estevez and Kulla's "Charlie" distribution of fibres sticking out of cloth, which reflect
+// mostly towards grazing angles.
+#[derive(Clone, Copy)]
+pub struct Charlie {
+    alpha: Float,
+}
+
+// Resolution of the table of directional albedo over the cosine and alpha.
+const CHARLIE_ALBEDO_SIZE: usize = 32;
+
+impl Charlie {
+    pub fn new(alpha: Float) -> Self {
+        Self {
+            alpha: alpha.clamp(1.0e-3, 1.0),
+        }
+    }
+
+    pub fn d(&self, wm: Vec3) -> Float {
+        let inverse = 1.0 / self.alpha;
+        let sin_theta = (1.0 - wm.z * wm.z).max(0.0).sqrt();
+        (2.0 + inverse) * sin_theta.powf(inverse) / (2.0 * PI)
+    }
+
+    // Fit of the shadowing of fibres by their neighbours.
+    fn lambda(&self, cos_theta: Float) -> Float {
+        let t = (1.0 - self.alpha) * (1.0 - self.alpha);
+        let fit = |x: Float| {
+            let p = |low: Float, high: Float| low + (high - low) * t;
+            let (a, b, c, d, e) = (
+                p(21.5473, 25.3245),
+                p(3.82987, 3.32435),
+                p(0.19823, 0.16801),
+                p(-1.97760, -1.27393),
+                p(-4.32054, -4.85967),
+            );
+            a / (1.0 + b * x.powf(c)) + d * x + e
+        };
+        let cos_theta = cos_theta.abs();
+        if cos_theta < 0.5 {
+            fit(cos_theta).exp()
+        } else {
+            (2.0 * fit(0.5) - fit(1.0 - cos_theta)).exp()
+        }
+    }
+
+    pub fn g(&self, wo: Vec3, wi: Vec3) -> Float {
+        1.0 / (1.0 + self.lambda(wo.z) + self.lambda(wi.z))
+    }
+
+    // A normal sampled in proportion to `d` times its cosine, which has a closed form inverse.
+    pub fn sample_normal(&self, u: Vec2) -> Vec3 {
+        let sin_theta = u.x.powf(1.0 / (1.0 / self.alpha + 2.0));
+        let cos_theta = (1.0 - sin_theta * sin_theta).max(0.0).sqrt();
+        let phi = 2.0 * PI * u.y;
+        Vec3::new(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta)
+    }
+
+    // Share of light reflected by white fibres seen at `cos_theta` from the normal, interpolated
+    // from a table computed the first time it is needed.
+    pub fn albedo(&self, cos_theta: Float) -> Float {
+        static TABLE: OnceLock<Vec<Float>> = OnceLock::new();
+        let n = CHARLIE_ALBEDO_SIZE;
+        let table = TABLE.get_or_init(|| {
+            (0..n * n)
+                .map(|i| {
+                    let cos_theta = (i % n) as Float / (n - 1) as Float;
+                    let alpha = (i / n) as Float / (n - 1) as Float;
+                    Charlie::new(alpha).integrate_albedo(cos_theta.max(1.0e-3))
+                })
+                .collect()
+        });
+        let x = cos_theta.clamp(0.0, 1.0) * (n - 1) as Float;
+        let y = self.alpha * (n - 1) as Float;
+        let (x0, y0) = ((x as usize).min(n - 2), (y as usize).min(n - 2));
+        let (tx, ty) = (x - x0 as Float, y - y0 as Float);
+        let at = |x: usize, y: usize| table[y * n + x];
+        let lerp = |a: Float, b: Float, t: Float| a + (b - a) * t;
+        lerp(
+            lerp(at(x0, y0), at(x0 + 1, y0), tx),
+            lerp(at(x0, y0 + 1), at(x0 + 1, y0 + 1), tx),
+            ty,
+        )
+    }
+
+    fn integrate_albedo(&self, cos_theta: Float) -> Float {
+        let wo = Vec3::new((1.0 - cos_theta * cos_theta).sqrt(), 0.0, cos_theta);
+        let steps = 64;
+        let mut sum = 0.0;
+        for i in 0..steps {
+            let theta = (i as Float + 0.5) / steps as Float * PI / 2.0;
+            for j in 0..steps {
+                // Reflection is symmetric about the plane of `wo` and the normal.
+                let phi = (j as Float + 0.5) / steps as Float * PI;
+                let wi = Vec3::new(
+                    theta.sin() * phi.cos(),
+                    theta.sin() * phi.sin(),
+                    theta.cos(),
+                );
+                let wm = (wo + wi).normalize();
+                sum += self.d(wm) * self.g(wo, wi) / (4.0 * wo.z) * theta.sin();
+            }
+        }
+        (sum * 2.0 * (PI / 2.0 / steps as Float) * (PI / steps as Float)).min(1.0)
+    }
+}
@@ -1,12 +1,21 @@
 use crate::{Float, PI};
 
-use crate::Vec3;
+use crate::{Vec2, Vec3};
 
-use crate::{hittable::Hittable, onb::ONB, rand_vec3::random_cosine_direction};
+use crate::{
+    fresnel,
+    hittable::Hittable,
+    microfacet::{Charlie, TrowbridgeReitz},
+    onb::ONB,
+    rand_vec3::{random_cosine_direction, reflect, refract},
+    sampler::random,
+};
 
 pub trait PDF {
     fn value(&self, direction: &Vec3) -> Float;
-    fn generate(&self) -> Vec3;
+    // A direction drawn with density `value`, or `None` for a draw that found no direction, which
+    // densities that lose some of their samples count as having been made.
+    fn generate(&self) -> Option<Vec3>;
 }
 
 pub struct CosinePDF {
@@ -25,8 +34,8 @@ impl PDF for CosinePDF {
         return (cosine_theta / PI).max(0.0);
     }
 
-    fn generate(&self) -> Vec3 {
-        self.uvw.transform(&random_cosine_direction())
+    fn generate(&self) -> Option<Vec3> {
+        Some(self.uvw.transform(&random_cosine_direction()))
     }
 }
 
@@ -46,8 +55,8 @@ impl<'a> PDF for HittablePDF<'a> {
         self.objects.pdf_value(&self.origin, direction)
     }
 
-    fn generate(&self) -> Vec3 {
-        self.objects.random_vector_to_surface(&self.origin)
+    fn generate(&self) -> Option<Vec3> {
+        Some(self.objects.random_vector_to_surface(&self.origin))
     }
 }
 
@@ -63,8 +72,8 @@ impl<'a> MixturePDF<'a> {
 }
 
 impl<'a> PDF for MixturePDF<'a> {
-    fn generate(&self) -> Vec3 {
-        let r: Float = rand::random();
+    fn generate(&self) -> Option<Vec3> {
+        let r = random();
         if r < 0.5 {
             self.a.generate()
         } else {
@@ -80,3 +89,385 @@ impl<'a> PDF for MixturePDF<'a> {
         self.a.value(direction) * 0.5 + self.b.value(direction) * 0.5
     }
 }
+
+// Picks one of several densities in proportion to its weight.
+pub struct WeightedMixturePDF {
+    components: Vec<(Float, Box<dyn PDF>)>,
+}
+
+impl WeightedMixturePDF {
+    // Weights need not add up to one. There must be at least one positive weight.
+    pub fn new(components: Vec<(Float, Box<dyn PDF>)>) -> Self {
+        let total: Float = components.iter().map(|(w, _)| w.max(0.0)).sum();
+        assert!(total > 0.0, "WeightedMixturePDF needs a positive weight");
+        Self {
+            components: components
+                .into_iter()
+                .filter(|(w, _)| *w > 0.0)
+                .map(|(w, pdf)| (w / total, pdf))
+                .collect(),
+        }
+    }
+}
+
+impl PDF for WeightedMixturePDF {
+    fn value(&self, direction: &Vec3) -> Float {
+        self.components
+            .iter()
+            .map(|(w, pdf)| w * pdf.value(direction))
+            .sum()
+    }
+
+    fn generate(&self) -> Option<Vec3> {
+        let mut u = random();
+        for (w, pdf) in &self.components {
+            if u < *w {
+                return pdf.generate();
+            }
+            u -= w;
+        }
+        self.components.last().unwrap().1.generate()
+    }
+}
+
+// Henyey-Greenstein phase function around the direction light was travelling in.
+pub struct HenyeyGreensteinPDF {
+    uvw: ONB,
+    g: Float,
+}
+
+impl HenyeyGreensteinPDF {
+    pub fn new(direction: &Vec3, g: Float) -> Self {
+        Self {
+            uvw: ONB::new(direction),
+            g: g.clamp(-0.99, 0.99),
+        }
+    }
+
+    pub fn phase(g: Float, cos_theta: Float) -> Float {
+        let denom = 1.0 + g * g - 2.0 * g * cos_theta;
+        (1.0 - g * g) / (4.0 * PI * denom * denom.max(0.0).sqrt())
+    }
+}
+
+impl PDF for HenyeyGreensteinPDF {
+    fn value(&self, direction: &Vec3) -> Float {
+        Self::phase(self.g, direction.normalize().dot(self.uvw.w()))
+    }
+
+    fn generate(&self) -> Option<Vec3> {
+        let g = self.g;
+        let u = random();
+        let cos_theta = if g.abs() < 1.0e-3 {
+            1.0 - 2.0 * u
+        } else {
+            let s = (1.0 - g * g) / (1.0 - g + 2.0 * g * u);
+            ((1.0 + g * g - s * s) / (2.0 * g)).clamp(-1.0, 1.0)
+        };
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        let phi = 2.0 * PI * random();
+        Some(self.uvw.transform(&Vec3::new(
+            sin_theta * phi.cos(),
+            sin_theta * phi.sin(),
+            cos_theta,
+        )))
+    }
+}
+
+// Reflection off the microfacets visible from `wo`, given in the local frame of `uvw`.
+pub struct MicrofacetPDF {
+    uvw: ONB,
+    wo: Vec3,
+    distribution: TrowbridgeReitz,
+}
+
+impl MicrofacetPDF {
+    pub fn new(uvw: ONB, wo: Vec3, distribution: TrowbridgeReitz) -> Self {
+        Self {
+            uvw,
+            wo,
+            distribution,
+        }
+    }
+}
+
+impl PDF for MicrofacetPDF {
+    fn value(&self, direction: &Vec3) -> Float {
+        let wi = self.uvw.to_local(&direction.normalize());
+        if wi.z <= 0.0 {
+            return 0.0;
+        }
+        let wm = (self.wo + wi).normalize();
+        self.distribution.d_visible(self.wo, wm) / (4.0 * self.wo.dot(wm))
+    }
+
+    fn generate(&self) -> Option<Vec3> {
+        let wm = self
+            .distribution
+            .sample_visible_normal(self.wo, Vec2::new(random(), random()));
+        let wi = reflect(&-self.wo, &wm);
+        // Light reflected into the surface is lost.
+        if wi.z <= 0.0 {
+            return None;
+        }
+        Some(self.uvw.transform(&wi))
+    }
+}
+
+// Reflection and transmission through the microfacets visible from `wo` of a rough boundary into
+// a dielectric of relative index of refraction `eta`, each in proportion to its reflectance.
+pub struct RoughDielectricPDF {
+    uvw: ONB,
+    wo: Vec3,
+    eta: Float,
+    distribution: TrowbridgeReitz,
+}
+
+impl RoughDielectricPDF {
+    pub fn new(uvw: ONB, wo: Vec3, eta: Float, distribution: TrowbridgeReitz) -> Self {
+        Self {
+            uvw,
+            wo,
+            eta,
+            distribution,
+        }
+    }
+
+    // The microfacet normal that scatters `wo` into `wi`, both in the local frame, if any.
+    pub fn half_vector(wo: Vec3, wi: Vec3, eta: Float) -> Option<Vec3> {
+        let etap = if wi.z > 0.0 { 1.0 } else { eta };
+        let wm = wi * etap + wo;
+        if wi.z == 0.0 || wm.length_squared() == 0.0 {
+            return None;
+        }
+        let wm = wm.normalize();
+        let wm = if wm.z < 0.0 { -wm } else { wm };
+        // Microfacets seen from behind scatter nothing.
+        if wm.dot(wi) * wi.z < 0.0 || wm.dot(wo) * wo.z < 0.0 {
+            return None;
+        }
+        Some(wm)
+    }
+}
+
+impl PDF for RoughDielectricPDF {
+    fn value(&self, direction: &Vec3) -> Float {
+        let wi = self.uvw.to_local(&direction.normalize());
+        let Some(wm) = Self::half_vector(self.wo, wi, self.eta) else {
+            return 0.0;
+        };
+        let reflectance = fresnel::dielectric(self.wo.dot(wm), self.eta);
+        let visible = self.distribution.d_visible(self.wo, wm);
+        if wi.z > 0.0 {
+            visible / (4.0 * self.wo.dot(wm)) * reflectance
+        } else {
+            let denom = wi.dot(wm) + self.wo.dot(wm) / self.eta;
+            visible * wi.dot(wm).abs() / (denom * denom) * (1.0 - reflectance)
+        }
+    }
+
+    fn generate(&self) -> Option<Vec3> {
+        let wm = self
+            .distribution
+            .sample_visible_normal(self.wo, Vec2::new(random(), random()));
+        let reflected = random() < fresnel::dielectric(self.wo.dot(wm), self.eta);
+        let wi = if reflected {
+            reflect(&-self.wo, &wm)
+        } else {
+            refract(&-self.wo, &wm, 1.0 / self.eta)
+        };
+        // Light reflected into the surface or refracted out of it is lost.
+        if reflected != (wi.z > 0.0) {
+            return None;
+        }
+        Some(self.uvw.transform(&wi))
+    }
+}
+
+// Reflection off cloth fibres of the Charlie distribution, sampled in proportion to their
+// density rather than to what is visible from `wo`.
+pub struct CharliePDF {
+    uvw: ONB,
+    wo: Vec3,
+    distribution: Charlie,
+}
+
+impl CharliePDF {
+    pub fn new(uvw: ONB, wo: Vec3, distribution: Charlie) -> Self {
+        Self {
+            uvw,
+            wo,
+            distribution,
+        }
+    }
+}
+
+impl PDF for CharliePDF {
+    fn value(&self, direction: &Vec3) -> Float {
+        let wi = self.uvw.to_local(&direction.normalize());
+        if wi.z <= 0.0 {
+            return 0.0;
+        }
+        let wm = (self.wo + wi).normalize();
+        self.distribution.d(wm) * wm.z / (4.0 * self.wo.dot(wm))
+    }
+
+    fn generate(&self) -> Option<Vec3> {
+        let wm = self
+            .distribution
+            .sample_normal(Vec2::new(random(), random()));
+        let wi = reflect(&-self.wo, &wm);
+        if wi.z <= 0.0 || self.wo.dot(wm) <= 0.0 {
+            return None;
+        }
+        Some(self.uvw.transform(&wi))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    // Directions on a grid uniform in z and azimuth, whose cells all cover the same solid angle.
+    fn grid(rows: usize, columns: usize) -> impl Iterator<Item = (usize, usize, Vec3)> {
+        (0..rows).flat_map(move |row| {
+            (0..columns).map(move |column| {
+                let z = -1.0 + 2.0 * (row as Float + 0.5) / rows as Float;
+                let phi = 2.0 * PI * (column as Float + 0.5) / columns as Float;
+                let r = (1.0 - z * z).max(0.0).sqrt();
+                (row, column, Vec3::new(r * phi.cos(), r * phi.sin(), z))
+            })
+        })
+    }
+
+    // The integral of `pdf` over the sphere of directions.
+    pub(crate) fn integrate(pdf: impl Fn(Vec3) -> Float) -> f64 {
+        let (rows, columns) = (256, 512);
+        let cell = 4.0 * std::f64::consts::PI / (rows * columns) as f64;
+        grid(rows, columns)
+            .map(|(_, _, direction)| pdf(direction) as f64 * cell)
+            .sum()
+    }
+
+    // Checks that the directions drawn by `sample` fall into equal solid-angle cells as often as
+    // `pdf` predicts, and that draws fail as often as `pdf` integrates to less than one.
+    pub(crate) fn assert_samples_match(
+        sample: impl Fn() -> Option<Vec3>,
+        pdf: impl Fn(Vec3) -> Float,
+    ) {
+        const ROWS: usize = 16;
+        const COLUMNS: usize = 32;
+        const SUBDIVISIONS: usize = 32;
+        const SAMPLES: usize = 200_000;
+
+        let mut expected = [[0.0; COLUMNS]; ROWS];
+        let cell =
+            4.0 * std::f64::consts::PI / (ROWS * COLUMNS * SUBDIVISIONS * SUBDIVISIONS) as f64;
+        for (row, column, direction) in grid(ROWS * SUBDIVISIONS, COLUMNS * SUBDIVISIONS) {
+            expected[row / SUBDIVISIONS][column / SUBDIVISIONS] +=
+                pdf(direction) as f64 * cell * SAMPLES as f64;
+        }
+
+        let mut observed = [[0usize; COLUMNS]; ROWS];
+        let mut drawn = 0;
+        for _ in 0..SAMPLES {
+            let Some(direction) = sample() else {
+                continue;
+            };
+            let direction = direction.normalize();
+            assert!(
+                direction.is_finite(),
+                "Sampled a direction of {}",
+                direction
+            );
+            let row = (((direction.z + 1.0) / 2.0 * ROWS as Float) as usize).min(ROWS - 1);
+            let phi = direction.y.atan2(direction.x).rem_euclid(2.0 * PI);
+            let column = ((phi / (2.0 * PI) * COLUMNS as Float) as usize).min(COLUMNS - 1);
+            observed[row][column] += 1;
+            drawn += 1;
+        }
+
+        for row in 0..ROWS {
+            for column in 0..COLUMNS {
+                let (expected, observed) = (expected[row][column], observed[row][column] as f64);
+                // Cells cut by an edge of the density are integrated only roughly.
+                let tolerance = 5.0 * expected.sqrt() + 0.02 * expected + 2.0e-4 * SAMPLES as f64;
+                assert!(
+                    (observed - expected).abs() <= tolerance,
+                    "Cell {} {} has {} samples where {} are expected",
+                    row,
+                    column,
+                    observed,
+                    expected
+                );
+            }
+        }
+
+        let expected: f64 = expected.iter().flatten().sum();
+        let tolerance = 5.0 * (SAMPLES as f64).sqrt() + 0.01 * SAMPLES as f64;
+        assert!(
+            (drawn as f64 - expected).abs() <= tolerance,
+            "Drew {} samples where {} are expected",
+            drawn,
+            expected
+        );
+    }
+
+    #[test]
+    fn cosine_samples_match_their_density() {
+        let pdf = CosinePDF::new(ONB::new(&Vec3::new(0.3, -0.5, 0.8)));
+        assert!((integrate(|direction| pdf.value(&direction)) - 1.0).abs() < 1.0e-3);
+        assert_samples_match(|| pdf.generate(), |direction| pdf.value(&direction));
+    }
+
+    // The frame of a tilted surface.
+    pub(crate) fn frame() -> ONB {
+        ONB::new(&Vec3::new(-0.2, 0.4, 0.9))
+    }
+
+    // Directions leaving a surface, in its local frame.
+    pub(crate) fn outgoing() -> [Vec3; 3] {
+        [
+            Vec3::new(0.1, 0.2, 0.97).normalize(),
+            Vec3::new(0.6, -0.3, 0.5).normalize(),
+            Vec3::new(-0.9, 0.1, 0.15).normalize(),
+        ]
+    }
+
+    #[test]
+    fn microfacet_samples_match_their_density() {
+        for wo in outgoing() {
+            for (alpha_x, alpha_y) in [(0.3, 0.3), (0.6, 0.15)] {
+                let pdf = MicrofacetPDF::new(frame(), wo, TrowbridgeReitz::new(alpha_x, alpha_y));
+                let total = integrate(|direction| pdf.value(&direction));
+                assert!(total <= 1.0 + 1.0e-2, "The density integrates to {}", total);
+                assert_samples_match(|| pdf.generate(), |direction| pdf.value(&direction));
+            }
+        }
+    }
+
+    #[test]
+    fn rough_dielectric_samples_match_their_density() {
+        for wo in outgoing() {
+            for eta in [1.5, 1.0 / 1.5] {
+                let distribution = TrowbridgeReitz::new(0.3, 0.3);
+                let pdf = RoughDielectricPDF::new(frame(), wo, eta, distribution);
+                let total = integrate(|direction| pdf.value(&direction));
+                assert!(total <= 1.0 + 1.0e-2, "The density integrates to {}", total);
+                assert_samples_match(|| pdf.generate(), |direction| pdf.value(&direction));
+            }
+        }
+    }
+
+    #[test]
+    fn charlie_samples_match_their_density() {
+        for wo in outgoing() {
+            for alpha in [0.3, 0.8] {
+                let pdf = CharliePDF::new(frame(), wo, Charlie::new(alpha));
+                let total = integrate(|direction| pdf.value(&direction));
+                assert!(total <= 1.0 + 1.0e-2, "The density integrates to {}", total);
+                assert_samples_match(|| pdf.generate(), |direction| pdf.value(&direction));
+            }
+        }
+    }
+}
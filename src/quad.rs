@@ -1,15 +1,98 @@
+use crate::sampler::random;
 use crate::{
     aabb::AABB,
-    hittable::{HitRecord, Hittable},
+    hittable::{HitRecord, Hittable, NormalCone, SurfaceSample},
     interval::Interval,
     materials::material::Material,
     ray::Ray,
-    Float,
+    Float, PI,
 };
 use crate::{Vec2, Vec3};
-use rand::random;
 use std::fmt::{Debug, Write};
 
+// Below this solid angle the quad is sampled by area, which is as good there and numerically safer.
+const MIN_SPHERICAL_SOLID_ANGLE: Float = 1.0e-3;
+
+// A rectangle as seen from a point, for sampling directions uniformly in its solid angle
+// (Urena, Fajardo and King, "An Area-Preserving Parametrization for Spherical Rectangles").
+struct SphericalRectangle {
+    x: Vec3,
+    y: Vec3,
+    z: Vec3,
+    x0: Float,
+    x1: Float,
+    y0: Float,
+    y1: Float,
+    z0: Float,
+    b0: Float,
+    b1: Float,
+    k: Float,
+    solid_angle: Float,
+}
+
+impl SphericalRectangle {
+    fn new(quad: &Quad, origin: Vec3) -> Option<Self> {
+        let (width, height) = (quad.u.length(), quad.v.length());
+        let x = quad.u / width;
+        let y = quad.v / height;
+        let mut z = x.cross(y);
+        let d = quad.q - origin;
+        let mut z0 = d.dot(z);
+        if z0 > 0.0 {
+            z0 = -z0;
+            z = -z;
+        }
+        let (x0, y0) = (d.dot(x), d.dot(y));
+        let (x1, y1) = (x0 + width, y0 + height);
+
+        let n0 = Vec3::new(0.0, z0, -y0).try_normalize()?;
+        let n1 = Vec3::new(-z0, 0.0, x1).try_normalize()?;
+        let n2 = Vec3::new(0.0, -z0, y1).try_normalize()?;
+        let n3 = Vec3::new(z0, 0.0, -x0).try_normalize()?;
+        let g0 = (-n0.dot(n1)).clamp(-1.0, 1.0).acos();
+        let g1 = (-n1.dot(n2)).clamp(-1.0, 1.0).acos();
+        let g2 = (-n2.dot(n3)).clamp(-1.0, 1.0).acos();
+        let g3 = (-n3.dot(n0)).clamp(-1.0, 1.0).acos();
+        let k = 2.0 * PI - g2 - g3;
+        let solid_angle = g0 + g1 - k;
+
+        Some(Self {
+            x,
+            y,
+            z,
+            x0,
+            x1,
+            y0,
+            y1,
+            z0,
+            b0: n0.z,
+            b1: n2.z,
+            k,
+            solid_angle,
+        })
+    }
+
+    fn sample(&self) -> Vec3 {
+        let au = random() * self.solid_angle + self.k;
+        let fu = (au.cos() * self.b0 - self.b1) / au.sin();
+        let cu = (fu.signum() / (fu * fu + self.b0 * self.b0).sqrt()).clamp(-1.0, 1.0);
+        let xu = (-(cu * self.z0) / (1.0 - cu * cu).max(0.0).sqrt()).clamp(self.x0, self.x1);
+
+        let d = (xu * xu + self.z0 * self.z0).sqrt();
+        let h0 = self.y0 / (d * d + self.y0 * self.y0).sqrt();
+        let h1 = self.y1 / (d * d + self.y1 * self.y1).sqrt();
+        let hv = h0 + random() * (h1 - h0);
+        let yv = if hv * hv < 1.0 - 1.0e-6 {
+            hv * d / (1.0 - hv * hv).sqrt()
+        } else {
+            self.y1
+        };
+
+        // The offset from the origin to the sampled point.
+        xu * self.x + yv * self.y + self.z0 * self.z
+    }
+}
+
 pub struct Quad<'a> {
     q: Vec3,
     u: Vec3,
@@ -46,6 +129,16 @@ impl<'a> Quad<'a> {
         AABB::from_extrema(q, q + u + v).to_contain(&AABB::from_extrema(q + u, q + v))
     }
 
+    // Solid angle sampling needs a rectangle; parallelograms and far away quads use area sampling.
+    fn spherical_rectangle(&self, origin: Vec3) -> Option<SphericalRectangle> {
+        if self.u.normalize().dot(self.v.normalize()).abs() > 1.0e-4 {
+            return None;
+        }
+        SphericalRectangle::new(self, origin).filter(|rectangle| {
+            rectangle.solid_angle.is_finite() && rectangle.solid_angle > MIN_SPHERICAL_SOLID_ANGLE
+        })
+    }
+
     fn is_interior(&self, alpha: Float, beta: Float) -> Option<Vec2> {
         let unit_interval = Interval::new(0.0, 1.0);
 
@@ -107,6 +200,10 @@ impl<'a> Hittable for Quad<'a> {
             &Ray::new(*origin, *direction),
             Interval::new(0.0001, Float::MAX),
         ) {
+            if let Some(rectangle) = self.spherical_rectangle(*origin) {
+                return 1.0 / rectangle.solid_angle;
+            }
+
             let distance_squared = hr.t * hr.t * direction.length_squared();
             let cosine = direction.dot(hr.normal).abs() / direction.length();
 
@@ -117,9 +214,49 @@ impl<'a> Hittable for Quad<'a> {
     }
 
     fn random_vector_to_surface(&self, origin: &Vec3) -> Vec3 {
-        let p = self.q + self.u * random::<Float>() + self.v * random::<Float>();
+        if let Some(rectangle) = self.spherical_rectangle(*origin) {
+            return rectangle.sample();
+        }
+        let p = self.q + self.u * random() + self.v * random();
         return p - *origin;
     }
+
+    fn sample_surface(&self) -> Option<SurfaceSample> {
+        Some(SurfaceSample {
+            p: self.q + self.u * random() + self.v * random(),
+            normal: self.normal,
+            pdf: 1.0 / self.area,
+        })
+    }
+
+    fn surface_pdf_value(&self, origin: &Vec3, direction: &Vec3) -> Float {
+        if self
+            .hit(
+                &Ray::new(*origin, *direction),
+                Interval::new(0.0001, Float::MAX),
+            )
+            .is_some()
+        {
+            return 1.0 / self.area;
+        }
+
+        0.0
+    }
+
+    fn normal_cone(&self) -> NormalCone {
+        NormalCone {
+            axis: self.normal,
+            cos_theta: 1.0,
+        }
+    }
+
+    fn emitters(&self) -> Vec<&dyn Hittable> {
+        if self.material.is_emissive() {
+            vec![self]
+        } else {
+            vec![]
+        }
+    }
 }
 
 impl<'a> Debug for Quad<'a> {
@@ -129,3 +266,61 @@ impl<'a> Debug for Quad<'a> {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        color::Color,
+        materials::lambert::Lambert,
+        pdf::tests::{assert_samples_match, integrate},
+        texture::ColorTexture2D,
+    };
+
+    fn assert_sampling_matches(quad: &Quad, origin: Vec3) {
+        let total = integrate(|direction| quad.pdf_value(&origin, &direction));
+        assert!(
+            (total - 1.0).abs() < 1.0e-2,
+            "The density integrates to {}",
+            total
+        );
+        assert_samples_match(
+            || Some(quad.random_vector_to_surface(&origin)),
+            |direction| quad.pdf_value(&origin, &direction),
+        );
+    }
+
+    #[test]
+    fn spherical_rectangle_samples_match_the_density() {
+        let albedo = ColorTexture2D {
+            color: Color::new(0.5, 0.5, 0.5),
+        };
+        let material = Lambert::new(&albedo);
+        let quad = Quad::new(
+            Vec3::new(-0.5, -1.0, 1.0),
+            Vec3::new(2.0, 0.0, 0.5),
+            Vec3::new(0.0, 1.5, 0.0),
+            &material,
+        );
+        let origin = Vec3::new(0.3, 0.2, 0.1);
+        assert!(quad.spherical_rectangle(origin).is_some());
+        assert_sampling_matches(&quad, origin);
+    }
+
+    #[test]
+    fn parallelogram_samples_match_the_density() {
+        let albedo = ColorTexture2D {
+            color: Color::new(0.5, 0.5, 0.5),
+        };
+        let material = Lambert::new(&albedo);
+        let quad = Quad::new(
+            Vec3::new(-0.5, -1.0, 1.0),
+            Vec3::new(2.0, 0.0, 0.0),
+            Vec3::new(0.5, 1.5, 0.0),
+            &material,
+        );
+        let origin = Vec3::new(0.3, 0.2, 0.1);
+        assert!(quad.spherical_rectangle(origin).is_none());
+        assert_sampling_matches(&quad, origin);
+    }
+}
@@ -1,19 +1,19 @@
+use crate::sampler::random;
 use crate::Vec3;
 use crate::{Float, PI};
-use rand::prelude::*;
 pub fn random_vec() -> Vec3 {
     Vec3 {
-        x: random::<Float>(),
-        y: random::<Float>(),
-        z: random::<Float>(),
+        x: random(),
+        y: random(),
+        z: random(),
     }
 }
 
 pub fn random_vec_range(min: Float, max: Float) -> Vec3 {
     Vec3 {
-        x: min + (max - min) * random::<Float>(),
-        y: min + (max - min) * random::<Float>(),
-        z: min + (max - min) * random::<Float>(),
+        x: min + (max - min) * random(),
+        y: min + (max - min) * random(),
+        z: min + (max - min) * random(),
     }
 }
 
@@ -59,8 +59,8 @@ pub fn random_vec_unit_disk() -> Vec3 {
 }
 
 pub fn random_cosine_direction() -> Vec3 {
-    let r1 = random::<Float>();
-    let r2 = random::<Float>();
+    let r1 = random();
+    let r2 = random();
 
     let phi = 2.0 * PI * r1;
     let x = Float::cos(phi) * Float::sqrt(r2);
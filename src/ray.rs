@@ -3,11 +3,22 @@ use crate::{Float, Vec3};
 pub struct Ray {
     pub origin: Vec3,
     pub direction: Vec3,
+    // The wavelength in nanometres a spectral render follows along the ray.
+    pub wavelength: Option<Float>,
 }
 
 impl Ray {
     pub fn new(origin: Vec3, direction: Vec3) -> Self {
-        Self { origin, direction }
+        Self {
+            origin,
+            direction,
+            wavelength: None,
+        }
+    }
+
+    pub fn with_wavelength(mut self, wavelength: Option<Float>) -> Self {
+        self.wavelength = wavelength;
+        self
     }
 
     pub fn at(&self, t: Float) -> Vec3 {
@@ -0,0 +1,56 @@
+use crate::{
+    camera::Camera, color::Color, hittable::Hittable, interval::Interval, onb::ONB,
+    rand_vec3::random_cosine_direction, ray::Ray, render_parameters::RenderParameters, Float,
+};
+
+use super::rayon::render_with;
+
+#[derive(Clone, Copy)]
+pub struct AmbientOcclusionParameters {
+    pub radius: Float,
+    pub num_samples: i32,
+}
+
+pub fn render(
+    camera: &Camera,
+    world: &dyn Hittable,
+    ao_params: AmbientOcclusionParameters,
+    render_params: RenderParameters,
+) -> Vec<Vec<Color>> {
+    render_with(camera, render_params, |ray| {
+        ray_color(ray, world, ao_params, render_params.background_color)
+    })
+}
+
+fn ray_color(
+    ray: &Ray,
+    world: &dyn Hittable,
+    ao_params: AmbientOcclusionParameters,
+    background_color: Color,
+) -> Color {
+    let Some(rec) = world.hit(
+        ray,
+        Interval {
+            min: 0.001,
+            max: Float::MAX,
+        },
+    ) else {
+        return background_color;
+    };
+
+    // Cosine-weighted hemisphere sampling makes the estimator a plain ratio of unoccluded rays.
+    let uvw = ONB::new(&rec.normal);
+    let mut unoccluded = 0;
+    for _ in 0..ao_params.num_samples {
+        let probe = Ray::new(rec.p, uvw.transform(&random_cosine_direction()));
+        if world
+            .hit(&probe, Interval::new(0.001, ao_params.radius))
+            .is_none()
+        {
+            unoccluded += 1;
+        }
+    }
+
+    let visibility = unoccluded as Float / ao_params.num_samples.max(1) as Float;
+    Color::new(visibility, visibility, visibility)
+}
@@ -0,0 +1,568 @@
+use crate::{
+    camera::Camera,
+    color::Color,
+    hittable::{HitRecord, Hittable},
+    interval::Interval,
+    light_list::emission_towards,
+    ray::Ray,
+    render_parameters::RenderParameters,
+    Float, Vec3,
+};
+
+use super::{
+    emission::{emission_direction_pdf, sample_emission},
+    film::Film,
+    rayon::render_with,
+};
+
+pub fn render(
+    camera: &Camera,
+    world: &dyn Hittable,
+    importants: &dyn Hittable,
+    render_params: RenderParameters,
+) -> Vec<Vec<Color>> {
+    let context = Context {
+        camera,
+        world,
+        lights: importants,
+        max_depth: render_params.max_depth,
+        background_color: render_params.background_color,
+    };
+    let film = Film::new(render_params.image_width, render_params.image_height);
+
+    let mut image = render_with(camera, render_params, |ray| {
+        let mut color = context.sample(ray, &film);
+        color.correct_nans();
+        color
+    });
+    film.add_to(&mut image);
+
+    image
+}
+
+struct Context<'a> {
+    camera: &'a Camera,
+    world: &'a dyn Hittable,
+    lights: &'a dyn Hittable,
+    max_depth: i32,
+    background_color: Color,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum VertexKind {
+    Camera,
+    Light,
+    Surface,
+}
+
+struct SurfaceInteraction<'a> {
+    rec: HitRecord<'a>,
+    r_in: Ray,
+    color: Color,
+}
+
+struct Vertex<'a> {
+    kind: VertexKind,
+    p: Vec3,
+    normal: Vec3,
+    beta: Color,
+    // Densities of generating this vertex from either neighbour, in area measure.
+    pdf_fwd: Float,
+    pdf_rev: Float,
+    delta: bool,
+    surface: Option<SurfaceInteraction<'a>>,
+}
+
+impl<'a> Vertex<'a> {
+    fn new(kind: VertexKind, p: Vec3, normal: Vec3, beta: Color, pdf_fwd: Float) -> Self {
+        Self {
+            kind,
+            p,
+            normal,
+            beta,
+            pdf_fwd,
+            pdf_rev: 0.0,
+            delta: false,
+            surface: None,
+        }
+    }
+
+    fn is_connectible(&self) -> bool {
+        match self.kind {
+            VertexKind::Camera | VertexKind::Light => true,
+            VertexKind::Surface => !self.delta,
+        }
+    }
+
+    // BSDF value (without the cosine term) for light leaving towards `direction`.
+    fn f(&self, direction: Vec3) -> Color {
+        let Some(si) = &self.surface else {
+            return Color::new(0.0, 0.0, 0.0);
+        };
+        let cos_theta = si.rec.normal.dot(direction.normalize()).abs();
+        if cos_theta < 1.0e-8 {
+            return Color::new(0.0, 0.0, 0.0);
+        }
+        let scattered = Ray::new(si.rec.p, direction);
+        si.rec
+            .material
+            .scattering_color(&si.r_in, &si.rec, &scattered, si.color)
+            / cos_theta
+    }
+}
+
+#[derive(Clone, Copy)]
+struct PathDensities {
+    pdf_fwd: Float,
+    pdf_rev: Float,
+    delta: bool,
+}
+
+impl From<&Vertex<'_>> for PathDensities {
+    fn from(v: &Vertex) -> Self {
+        Self {
+            pdf_fwd: v.pdf_fwd,
+            pdf_rev: v.pdf_rev,
+            delta: v.delta,
+        }
+    }
+}
+
+fn convert_density(pdf: Float, from: Vec3, to: &Vertex) -> Float {
+    let w = to.p - from;
+    let dist_squared = w.length_squared();
+    if dist_squared == 0.0 {
+        return 0.0;
+    }
+    let mut pdf = pdf / dist_squared;
+    if to.kind != VertexKind::Camera {
+        pdf *= to.normal.dot(w.normalize()).abs();
+    }
+    pdf
+}
+
+// Directional density with which the material at `rec` samples `outgoing` for a ray arriving along `incoming`.
+fn material_pdf(rec: &HitRecord, incoming: Vec3, outgoing: Vec3) -> Float {
+    let outward_normal = if rec.front_face {
+        rec.normal
+    } else {
+        -rec.normal
+    };
+    let r_in = Ray::new(rec.p - incoming, incoming);
+    let facing = HitRecord::new(rec.p, rec.t, outward_normal, &r_in, rec.material, rec.uv);
+    rec.material
+        .sampling_pdf(&r_in, &facing, &Ray::new(rec.p, outgoing))
+        .unwrap_or(0.0)
+}
+
+fn remap0(f: Float) -> Float {
+    if f != 0.0 {
+        f
+    } else {
+        1.0
+    }
+}
+
+impl<'a> Context<'a> {
+    fn sample(&self, ray: &Ray, film: &Film) -> Color {
+        let (camera_path, escaped) = self.camera_subpath(ray);
+        let light_path = self.light_subpath();
+
+        let mut color = escaped;
+        for t in 1..=camera_path.len() {
+            for s in 0..=light_path.len() {
+                let depth = (s + t) as i32 - 2;
+                if (s == 1 && t == 1) || depth < 0 || depth > self.max_depth {
+                    continue;
+                }
+                let (contribution, raster) = self.connect(&light_path, &camera_path, s, t);
+                if let Some(raster) = raster {
+                    film.splat(raster, contribution);
+                } else {
+                    color += contribution;
+                }
+            }
+        }
+
+        color
+    }
+
+    fn camera_subpath(&self, ray: &Ray) -> (Vec<Vertex<'a>>, Color) {
+        let mut path = vec![Vertex::new(
+            VertexKind::Camera,
+            self.camera.center,
+            self.camera.forward(),
+            Color::new(1.0, 1.0, 1.0),
+            1.0,
+        )];
+        let pdf_dir = self.camera.direction_pdf(ray.direction);
+        let escaped = self.random_walk(
+            *ray,
+            Color::new(1.0, 1.0, 1.0),
+            pdf_dir,
+            self.max_depth + 1,
+            &mut path,
+        );
+        (
+            path,
+            escaped.map_or(Color::new(0.0, 0.0, 0.0), |beta| {
+                beta * self.background_color
+            }),
+        )
+    }
+
+    fn light_subpath(&self) -> Vec<Vertex<'a>> {
+        let mut path = vec![];
+        let Some(sample) = sample_emission(self.lights) else {
+            return path;
+        };
+
+        path.push(Vertex::new(
+            VertexKind::Light,
+            sample.p,
+            sample.normal,
+            sample.emitted,
+            sample.pdf_pos,
+        ));
+        let cos_theta = sample.normal.dot(sample.direction).abs();
+        let beta = sample.emitted * cos_theta / (sample.pdf_pos * sample.pdf_dir);
+        self.random_walk(
+            Ray::new(sample.p, sample.direction),
+            beta,
+            sample.pdf_dir,
+            self.max_depth,
+            &mut path,
+        );
+        path
+    }
+
+    // Extends `path` by tracing `ray`; returns the throughput of a camera path that escaped the scene.
+    fn random_walk(
+        &self,
+        ray: Ray,
+        beta: Color,
+        pdf: Float,
+        max_depth: i32,
+        path: &mut Vec<Vertex<'a>>,
+    ) -> Option<Color> {
+        let is_camera_path = path[0].kind == VertexKind::Camera;
+        let mut ray = ray;
+        let mut beta = beta;
+        let mut pdf_fwd = pdf;
+        let mut bounces = 0;
+        while bounces < max_depth {
+            let Some(rec) = self.world.hit(
+                &ray,
+                Interval {
+                    min: 0.001,
+                    max: Float::MAX,
+                },
+            ) else {
+                return if is_camera_path { Some(beta) } else { None };
+            };
+
+            let prev_p = path[path.len() - 1].p;
+            let mut vertex = Vertex::new(VertexKind::Surface, rec.p, rec.normal, beta, 0.0);
+            vertex.pdf_fwd = convert_density(pdf_fwd, prev_p, &vertex);
+            bounces += 1;
+
+            let Some(mat_hit_res) = rec.material.scatter(&ray, &rec) else {
+                // Emitters absorb, so only camera paths keep them as an s = 0 vertex.
+                if is_camera_path {
+                    vertex.kind = VertexKind::Light;
+                    vertex.surface = Some(SurfaceInteraction {
+                        rec,
+                        r_in: ray,
+                        color: Color::new(0.0, 0.0, 0.0),
+                    });
+                    path.push(vertex);
+                }
+                return None;
+            };
+
+            if bounces >= max_depth {
+                vertex.surface = Some(SurfaceInteraction {
+                    rec,
+                    r_in: ray,
+                    color: mat_hit_res.color,
+                });
+                path.push(vertex);
+                return None;
+            }
+
+            let pdf_rev;
+            let next;
+            if let Some(mat_pdf) = mat_hit_res.pdf {
+                let Some(direction) = mat_pdf.generate() else {
+                    path.push(vertex);
+                    return None;
+                };
+                let pdf = mat_pdf.value(&direction);
+                if pdf <= 0.0 {
+                    path.push(vertex);
+                    return None;
+                }
+                let scattered = Ray::new(rec.p, direction);
+                beta = beta
+                    * rec
+                        .material
+                        .scattering_color(&ray, &rec, &scattered, mat_hit_res.color)
+                    / pdf;
+                // The density of sampling `direction` at all, as materials such as Coated only
+                // sample it on some of their branches.
+                pdf_fwd = material_pdf(&rec, ray.direction, direction);
+                pdf_rev = material_pdf(&rec, -direction, -ray.direction);
+                next = scattered;
+            } else {
+                // Specular materials may continue from elsewhere, as subsurface scattering does.
+                next = mat_hit_res.ray;
+                beta = beta * mat_hit_res.color;
+                vertex.delta = true;
+                pdf_fwd = 0.0;
+                pdf_rev = 0.0;
+            }
+
+            let p = rec.p;
+            vertex.surface = Some(SurfaceInteraction {
+                rec,
+                r_in: ray,
+                color: mat_hit_res.color,
+            });
+            path.push(vertex);
+            let n = path.len();
+            path[n - 2].pdf_rev = convert_density(pdf_rev, p, &path[n - 2]);
+
+            if beta.is_black() {
+                return None;
+            }
+            ray = next;
+        }
+        None
+    }
+
+    fn visible(&self, a: Vec3, b: Vec3) -> bool {
+        let d = b - a;
+        let distance = d.length();
+        self.world
+            .hit(
+                &Ray::new(a, d / distance),
+                Interval::new(0.001, distance - 0.001),
+            )
+            .is_none()
+    }
+
+    fn pdf_light(&self, light: &Vertex, next: &Vertex) -> Float {
+        let pdf_dir = emission_direction_pdf(light.normal, next.p - light.p);
+        convert_density(pdf_dir, light.p, next)
+    }
+
+    fn pdf_light_origin(&self, light: &Vertex, from: &Vertex) -> Float {
+        self.lights.surface_pdf_value(&from.p, &(light.p - from.p))
+    }
+
+    fn pdf(&self, v: &Vertex, prev: Option<&Vertex>, next: &Vertex) -> Float {
+        match v.kind {
+            VertexKind::Light => self.pdf_light(v, next),
+            VertexKind::Camera => {
+                convert_density(self.camera.direction_pdf(next.p - v.p), v.p, next)
+            }
+            VertexKind::Surface => {
+                let (Some(si), Some(prev)) = (&v.surface, prev) else {
+                    return 0.0;
+                };
+                let pdf = material_pdf(&si.rec, v.p - prev.p, next.p - v.p);
+                convert_density(pdf, v.p, next)
+            }
+        }
+    }
+
+    fn connect(
+        &self,
+        light_path: &[Vertex<'a>],
+        camera_path: &[Vertex<'a>],
+        s: usize,
+        t: usize,
+    ) -> (Color, Option<(i32, i32)>) {
+        let black = Color::new(0.0, 0.0, 0.0);
+        let mut sampled = None;
+        let mut raster = None;
+
+        if t > 1 && s != 0 && camera_path[t - 1].kind == VertexKind::Light {
+            return (black, None);
+        }
+
+        let color = if s == 0 {
+            let pt = &camera_path[t - 1];
+            match (&pt.surface, pt.kind) {
+                (Some(si), VertexKind::Light) => {
+                    si.rec.material.emit_color(&si.r_in, &si.rec) * pt.beta
+                }
+                _ => black,
+            }
+        } else if t == 1 {
+            let qs = &light_path[s - 1];
+            if !qs.is_connectible() {
+                return (black, None);
+            }
+            let Some(position) = self.camera.raster_position(qs.p) else {
+                return (black, None);
+            };
+            raster = Some(position);
+
+            let wi = self.camera.center - qs.p;
+            let dist_squared = wi.length_squared();
+            let wi = wi.normalize();
+            let cos_camera = self.camera.forward().dot(wi).abs();
+            let importance = self.camera.importance(-wi);
+            let camera_vertex = Vertex::new(
+                VertexKind::Camera,
+                self.camera.center,
+                self.camera.forward(),
+                Color::new(1.0, 1.0, 1.0) * (importance * cos_camera / dist_squared),
+                0.0,
+            );
+            let mut color = qs.beta * qs.f(wi) * camera_vertex.beta;
+            color = color * qs.normal.dot(wi).abs();
+            sampled = Some(camera_vertex);
+            if color.is_black() || !self.visible(qs.p, self.camera.center) {
+                black
+            } else {
+                color
+            }
+        } else if s == 1 {
+            let pt = &camera_path[t - 1];
+            if !pt.is_connectible() {
+                return (black, None);
+            }
+            let Some(sample) = self.lights.sample_surface() else {
+                return (black, None);
+            };
+            let wi = sample.p - pt.p;
+            let dist_squared = wi.length_squared();
+            let wi = wi.normalize();
+            let cos_light = sample.normal.dot(wi).abs();
+            if sample.pdf <= 0.0 || cos_light <= 0.0 {
+                return (black, None);
+            }
+            let pdf = sample.pdf * dist_squared / cos_light;
+            let emitted = emission_towards(self.lights, sample.p, -wi);
+            let mut light_vertex = Vertex::new(
+                VertexKind::Light,
+                sample.p,
+                sample.normal,
+                emitted / pdf,
+                0.0,
+            );
+            light_vertex.pdf_fwd = self.pdf_light_origin(&light_vertex, pt);
+            let color = pt.beta * pt.f(wi) * light_vertex.beta * pt.normal.dot(wi).abs();
+            sampled = Some(light_vertex);
+            if color.is_black() || !self.visible(pt.p, sample.p) {
+                black
+            } else {
+                color
+            }
+        } else {
+            let qs = &light_path[s - 1];
+            let pt = &camera_path[t - 1];
+            if !qs.is_connectible() || !pt.is_connectible() {
+                return (black, None);
+            }
+            let d = pt.p - qs.p;
+            let color = qs.beta * qs.f(d) * pt.f(-d) * pt.beta;
+            if color.is_black() || !self.visible(qs.p, pt.p) {
+                black
+            } else {
+                let w = d.normalize();
+                let g = qs.normal.dot(w).abs() * pt.normal.dot(w).abs() / d.length_squared();
+                color * g
+            }
+        };
+
+        if color.is_black() {
+            return (black, raster);
+        }
+        let weight = self.mis_weight(light_path, camera_path, sampled.as_ref(), s, t);
+        (color * weight, raster)
+    }
+
+    // Balance-heuristic weight computed from the ratios of reverse and forward densities along the path.
+    fn mis_weight(
+        &self,
+        light_path: &[Vertex],
+        camera_path: &[Vertex],
+        sampled: Option<&Vertex>,
+        s: usize,
+        t: usize,
+    ) -> Float {
+        if s + t == 2 {
+            return 1.0;
+        }
+
+        let mut light: Vec<PathDensities> = light_path[..s].iter().map(Into::into).collect();
+        let mut camera: Vec<PathDensities> = camera_path[..t].iter().map(Into::into).collect();
+
+        let qs = match (s, sampled) {
+            (0, _) => None,
+            (1, Some(sampled)) => Some(sampled),
+            _ => Some(&light_path[s - 1]),
+        };
+        let pt = match (t, sampled) {
+            (1, Some(sampled)) => sampled,
+            _ => &camera_path[t - 1],
+        };
+        let qs_minus = if s > 1 {
+            Some(&light_path[s - 2])
+        } else {
+            None
+        };
+        let pt_minus = if t > 1 {
+            Some(&camera_path[t - 2])
+        } else {
+            None
+        };
+
+        if let Some(qs) = qs {
+            light[s - 1] = qs.into();
+            light[s - 1].delta = false;
+        }
+        camera[t - 1] = pt.into();
+        camera[t - 1].delta = false;
+
+        camera[t - 1].pdf_rev = match qs {
+            Some(qs) => self.pdf(qs, qs_minus, pt),
+            None => pt_minus.map_or(0.0, |pt_minus| self.pdf_light_origin(pt, pt_minus)),
+        };
+        if let Some(pt_minus) = pt_minus {
+            camera[t - 2].pdf_rev = match qs {
+                Some(qs) => self.pdf(pt, Some(qs), pt_minus),
+                None => self.pdf_light(pt, pt_minus),
+            };
+        }
+        if let Some(qs) = qs {
+            light[s - 1].pdf_rev = self.pdf(pt, pt_minus, qs);
+        }
+        if let (Some(qs), Some(qs_minus)) = (qs, qs_minus) {
+            light[s - 2].pdf_rev = self.pdf(qs, Some(pt), qs_minus);
+        }
+
+        let mut sum_ri = 0.0;
+        let mut ri = 1.0;
+        for i in (1..t).rev() {
+            ri *= remap0(camera[i].pdf_rev) / remap0(camera[i].pdf_fwd);
+            if !camera[i].delta && !camera[i - 1].delta {
+                sum_ri += ri;
+            }
+        }
+
+        ri = 1.0;
+        for i in (0..s).rev() {
+            ri *= remap0(light[i].pdf_rev) / remap0(light[i].pdf_fwd);
+            let delta_light_vertex = i > 0 && light[i - 1].delta;
+            if !light[i].delta && !delta_light_vertex {
+                sum_ri += ri;
+            }
+        }
+
+        1.0 / (1.0 + sum_ri)
+    }
+}
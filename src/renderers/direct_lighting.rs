@@ -0,0 +1,281 @@
+use std::ops::{Add, Div, Mul};
+
+use crate::{
+    camera::Camera,
+    color::Color,
+    hittable::{HitRecord, Hittable},
+    interval::Interval,
+    lights::light::Light,
+    ray::Ray,
+    render_parameters::RenderParameters,
+    Float, Vec3,
+};
+
+use super::rayon::render_with;
+
+pub fn render(
+    camera: &Camera,
+    world: &dyn Hittable,
+    importants: &dyn Hittable,
+    lights: &[&dyn Light],
+    render_params: RenderParameters,
+) -> Vec<Vec<Color>> {
+    render_with(camera, render_params, |ray| {
+        ray_color(
+            ray,
+            world,
+            importants,
+            lights,
+            render_params.max_depth,
+            render_params.background_color,
+            RaySource::Camera,
+        )
+        .clamp()
+    })
+}
+
+fn ray_color(
+    ray: &Ray,
+    world: &dyn Hittable,
+    important_objs: &dyn Hittable,
+    lights: &[&dyn Light],
+    depth: i32,
+    background_color: Color,
+    source: RaySource,
+) -> Color {
+    if depth <= 0 {
+        return Color::new(0.0, 0.0, 0.0);
+    }
+    let Some(rec) = world.hit(
+        ray,
+        Interval {
+            min: 0.001,
+            max: Float::MAX,
+        },
+    ) else {
+        return background_color + escaped_light(ray, lights, source);
+    };
+
+    let Some(mat_hit_res) = rec.material.scatter(ray, &rec) else {
+        return rec.material.emit_color(ray, &rec);
+    };
+    // Emissive media both glow and scatter where a ray collides with them.
+    let emitted = rec.material.emit_color(ray, &rec);
+
+    // Specular surfaces cannot be lit by sampling a light, so follow them until a diffuse hit.
+    if mat_hit_res.pdf.is_none() {
+        return emitted
+            + mat_hit_res.color
+                * ray_color(
+                    &mat_hit_res.ray,
+                    world,
+                    important_objs,
+                    lights,
+                    depth - 1,
+                    background_color,
+                    RaySource::Specular,
+                );
+    }
+
+    emitted
+        + sample_light(ray, &rec, mat_hit_res.color, world, important_objs)
+        + sample_lights(ray, &rec, mat_hit_res.color, world, lights, &|_| 0.0)
+}
+
+// Estimates light arriving at `rec` directly from `important_objs` with a single light sample.
+pub fn sample_light(
+    ray: &Ray,
+    rec: &HitRecord,
+    color: Color,
+    world: &dyn Hittable,
+    important_objs: &dyn Hittable,
+) -> Color {
+    let to_light = Ray::new(rec.p, important_objs.random_vector_to_surface(&rec.p));
+    let light_pdf = important_objs.pdf_value(&to_light.origin, &to_light.direction);
+    if light_pdf <= 0.0 {
+        return Color::new(0.0, 0.0, 0.0);
+    }
+
+    let Some(light_rec) = world.hit(
+        &to_light,
+        Interval {
+            min: 0.001,
+            max: Float::MAX,
+        },
+    ) else {
+        return Color::new(0.0, 0.0, 0.0);
+    };
+    if light_rec.material.scatter(&to_light, &light_rec).is_some() {
+        return Color::new(0.0, 0.0, 0.0);
+    }
+
+    rec.material.scattering_color(ray, rec, &to_light, color)
+        * light_rec.material.emit_color(&to_light, &light_rec)
+        / light_pdf
+}
+
+pub fn power_heuristic(pdf: Float, other_pdf: Float) -> Float {
+    let (a, b) = (pdf * pdf, other_pdf * other_pdf);
+    if a + b <= 0.0 {
+        return 0.0;
+    }
+    a / (a + b)
+}
+
+// Light carried along a path, as colour channels or as a spectrum at the wavelengths it follows.
+pub trait Radiance:
+    Copy
+    + Default
+    + Add<Output = Self>
+    + Mul<Output = Self>
+    + Mul<Float, Output = Self>
+    + Div<Float, Output = Self>
+{
+}
+
+impl<T> Radiance for T where
+    T: Copy
+        + Default
+        + Add<Output = T>
+        + Mul<Output = T>
+        + Mul<Float, Output = T>
+        + Div<Float, Output = T>
+{
+}
+
+// Turns the colours met along a path into `R`, telling the colours of surfaces and media apart
+// from those of light.
+pub trait Convert<R>: Sync {
+    fn reflectance(&self, color: Color) -> R;
+    fn light(&self, color: Color) -> R;
+}
+
+// Colours carried as they are.
+pub struct AsColor;
+
+impl Convert<Color> for AsColor {
+    fn reflectance(&self, color: Color) -> Color {
+        color
+    }
+
+    fn light(&self, color: Color) -> Color {
+        color
+    }
+}
+
+// Estimates light arriving at `rec` from each of `lights`. Lights that rays can also reach are
+// weighted against `scatter_pdf`, the density of the direction sampling that could find them.
+pub fn sample_lights(
+    ray: &Ray,
+    rec: &HitRecord,
+    color: Color,
+    world: &dyn Hittable,
+    lights: &[&dyn Light],
+    scatter_pdf: &dyn Fn(&Vec3) -> Float,
+) -> Color {
+    sample_lights_as(ray, rec, color, world, lights, scatter_pdf, &AsColor)
+}
+
+// `sample_lights` with the surface colour and the light each turned into `R` by `convert`.
+pub fn sample_lights_as<R: Radiance>(
+    ray: &Ray,
+    rec: &HitRecord,
+    color: Color,
+    world: &dyn Hittable,
+    lights: &[&dyn Light],
+    scatter_pdf: &dyn Fn(&Vec3) -> Float,
+    convert: &dyn Convert<R>,
+) -> R {
+    lights.iter().fold(R::default(), |direct, light| {
+        let Some(sample) = light.sample_incident(&rec.p) else {
+            return direct;
+        };
+        if sample.pdf <= 0.0 || sample.radiance.is_black() {
+            return direct;
+        }
+
+        let to_light = Ray::new(rec.p, sample.direction);
+        if world
+            .hit(
+                &to_light,
+                Interval {
+                    min: 0.001,
+                    max: sample.distance * (1.0 - 1.0e-4),
+                },
+            )
+            .is_some()
+        {
+            return direct;
+        }
+
+        let weight = if light.is_delta() {
+            1.0
+        } else {
+            power_heuristic(sample.pdf, scatter_pdf(&sample.direction))
+        };
+        let scattering_color = rec.material.scattering_color(ray, rec, &to_light, color);
+        direct
+            + convert.reflectance(scattering_color) * convert.light(sample.radiance) * weight
+                / sample.pdf
+    })
+}
+
+// How a ray was generated, which decides how lights it reaches when leaving the scene count.
+#[derive(Clone, Copy)]
+pub enum RaySource {
+    Camera,
+    // Light sampling could not have found the direction of a specular bounce.
+    Specular,
+    // Scattered with the given density, which is weighed against that of sampling the lights.
+    Sampled(Float),
+}
+
+// Radiance from `lights` along a ray leaving the scene.
+pub fn escaped_light(r: &Ray, lights: &[&dyn Light], source: RaySource) -> Color {
+    escaped_light_as(r, lights, source, &AsColor)
+}
+
+// `escaped_light` with the light turned into `R` by `convert`.
+pub fn escaped_light_as<R: Radiance>(
+    r: &Ray,
+    lights: &[&dyn Light],
+    source: RaySource,
+    convert: &dyn Convert<R>,
+) -> R {
+    lights
+        .iter()
+        .filter(|light| !light.is_delta())
+        .fold(R::default(), |emitted, light| {
+            let weight = match source {
+                RaySource::Camera if !light.visible_to_camera() => 0.0,
+                RaySource::Camera | RaySource::Specular => 1.0,
+                RaySource::Sampled(pdf) => {
+                    power_heuristic(pdf, light.pdf_value(&r.origin, &r.direction))
+                }
+            };
+            emitted + convert.light(light.emit_color(r)) * weight
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn power_heuristic_weights_sum_to_one() {
+        for (a, b) in [(1.0, 1.0), (0.3, 2.0), (5.0, 0.0), (1.0e-3, 40.0)] {
+            let sum = power_heuristic(a, b) + power_heuristic(b, a);
+            assert!((sum - 1.0).abs() < 1.0e-5, "{} and {} sum to {}", a, b, sum);
+        }
+        assert_eq!(power_heuristic(2.0, 2.0), 0.5);
+        assert_eq!(power_heuristic(3.0, 0.0), 1.0);
+        assert_eq!(power_heuristic(0.0, 3.0), 0.0);
+        assert_eq!(power_heuristic(0.0, 0.0), 0.0);
+    }
+
+    #[test]
+    fn power_heuristic_favours_the_larger_density() {
+        assert!((power_heuristic(3.0, 1.0) - 0.9).abs() < 1.0e-6);
+        assert!(power_heuristic(1.0, 3.0) < power_heuristic(1.0, 2.0));
+    }
+}
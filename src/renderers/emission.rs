@@ -0,0 +1,44 @@
+use crate::{
+    color::Color, hittable::Hittable, light_list::emission_towards, onb::ONB,
+    rand_vec3::random_cosine_direction, sampler::random, Float, Vec3, PI,
+};
+
+pub struct EmissionSample {
+    pub p: Vec3,
+    pub normal: Vec3,
+    pub direction: Vec3,
+    pub emitted: Color,
+    pub pdf_pos: Float,
+    pub pdf_dir: Float,
+}
+
+// Picks a point on one of `lights` and a cosine-distributed direction leaving it.
+pub fn sample_emission(lights: &dyn Hittable) -> Option<EmissionSample> {
+    let sample = lights.sample_surface()?;
+
+    // Area lights emit from both faces, so pick a side before cosine sampling a direction.
+    let side = if random() < 0.5 {
+        sample.normal
+    } else {
+        -sample.normal
+    };
+    let direction = ONB::new(&side).transform(&random_cosine_direction());
+    let pdf_dir = emission_direction_pdf(sample.normal, direction);
+    let emitted = emission_towards(lights, sample.p, direction);
+    if sample.pdf <= 0.0 || pdf_dir <= 0.0 || emitted.is_black() {
+        return None;
+    }
+
+    Some(EmissionSample {
+        p: sample.p,
+        normal: sample.normal,
+        direction,
+        emitted,
+        pdf_pos: sample.pdf,
+        pdf_dir,
+    })
+}
+
+pub fn emission_direction_pdf(normal: Vec3, direction: Vec3) -> Float {
+    0.5 * normal.dot(direction.normalize()).abs() / PI
+}
@@ -0,0 +1,69 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::{color::Color, Float};
+
+// Accumulates contributions that land on arbitrary pixels, such as light paths connected to the camera.
+pub struct Film {
+    width: i32,
+    height: i32,
+    pixels: Vec<[AtomicU64; 3]>,
+}
+
+impl Film {
+    pub fn new(width: i32, height: i32) -> Self {
+        let pixels = (0..width * height)
+            .map(|_| {
+                [
+                    AtomicU64::new(0.0f64.to_bits()),
+                    AtomicU64::new(0.0f64.to_bits()),
+                    AtomicU64::new(0.0f64.to_bits()),
+                ]
+            })
+            .collect();
+        Self {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    pub fn splat(&self, (x, y): (i32, i32), color: Color) {
+        if x < 0 || y < 0 || x >= self.width || y >= self.height {
+            return;
+        }
+        let pixel = &self.pixels[(y * self.width + x) as usize];
+        for (channel, value) in pixel.iter().zip([color.r(), color.g(), color.b()]) {
+            if value.is_nan() || value == 0.0 {
+                continue;
+            }
+            atomic_add(channel, value as f64);
+        }
+    }
+
+    pub fn get(&self, (x, y): (i32, i32)) -> Color {
+        let pixel = &self.pixels[(y * self.width + x) as usize];
+        let [r, g, b] = pixel
+            .each_ref()
+            .map(|c| f64::from_bits(c.load(Ordering::Relaxed)) as Float);
+        Color::new(r, g, b)
+    }
+
+    pub fn add_to(&self, image: &mut [Vec<Color>]) {
+        for (y, row) in image.iter_mut().enumerate() {
+            for (x, color) in row.iter_mut().enumerate() {
+                *color += self.get((x as i32, y as i32));
+            }
+        }
+    }
+}
+
+pub fn atomic_add(target: &AtomicU64, value: f64) {
+    let mut current = target.load(Ordering::Relaxed);
+    loop {
+        let added = (f64::from_bits(current) + value).to_bits();
+        match target.compare_exchange_weak(current, added, Ordering::Relaxed, Ordering::Relaxed) {
+            Ok(_) => break,
+            Err(actual) => current = actual,
+        }
+    }
+}
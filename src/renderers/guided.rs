@@ -0,0 +1,112 @@
+use crate::{
+    aabb::AABB,
+    camera::Camera,
+    color::Color,
+    guiding::{GuidingField, GuidingRecorder},
+    hittable::{HitRecord, Hittable},
+    lights::light::Light,
+    pdf::PDF,
+    render_parameters::RenderParameters,
+    Float, Vec3,
+};
+
+use super::{
+    direct_lighting::{AsColor, RaySource},
+    rayon::{render_with, Guide, PathTracer},
+};
+
+#[derive(Clone, Copy)]
+pub struct GuidingParameters {
+    pub resolution: i32,
+    pub training_passes: i32,
+}
+
+// Renders in passes of doubling sample counts, each guided by the radiance learned in the previous one.
+// Guiding is learned over `bounds`, which should leave out fog filling all of space.
+pub fn render(
+    camera: &Camera,
+    world: &dyn Hittable,
+    bounds: AABB,
+    importants: &dyn Hittable,
+    lights: &[&dyn Light],
+    guiding_params: GuidingParameters,
+    render_params: RenderParameters,
+) -> Vec<Vec<Color>> {
+    let mut image = vec![
+        vec![Color::new(0.0, 0.0, 0.0); render_params.image_width as usize];
+        render_params.image_height as usize
+    ];
+    let mut field: Option<GuidingField> = None;
+    let mut remaining = render_params.num_samples;
+    let mut pass_samples = 1;
+    let mut pass = 0;
+
+    while remaining > 0 {
+        let training = pass < guiding_params.training_passes;
+        let num_samples = if training {
+            pass_samples.min(remaining)
+        } else {
+            remaining
+        };
+        let recorder = GuidingRecorder::new(bounds, guiding_params.resolution.max(1) as usize);
+        let guiding = Guiding {
+            field: field.as_ref(),
+            recorder: if training { Some(&recorder) } else { None },
+        };
+        let tracer = PathTracer {
+            world,
+            important_objs: importants,
+            lights,
+            background_color: render_params.background_color,
+            convert: &AsColor,
+            single_wavelength: &|light| light,
+            guide: Some(&guiding),
+        };
+
+        let pass_image = render_with(
+            camera,
+            RenderParameters {
+                num_samples,
+                ..render_params
+            },
+            |ray| {
+                tracer
+                    .ray_color(ray, render_params.max_depth, RaySource::Camera)
+                    .clamp()
+            },
+        );
+        for (row, pass_row) in image.iter_mut().zip(pass_image) {
+            for (color, pass_color) in row.iter_mut().zip(pass_row) {
+                *color += pass_color;
+            }
+        }
+
+        if training {
+            field = Some(recorder.build());
+        }
+        remaining -= num_samples;
+        pass_samples *= 2;
+        pass += 1;
+    }
+
+    image
+}
+
+// Teaches the field being trained and samples the one learned in the previous pass.
+struct Guiding<'a> {
+    field: Option<&'a GuidingField>,
+    recorder: Option<&'a GuidingRecorder>,
+}
+
+impl<'a> Guide<Color> for Guiding<'a> {
+    fn distribution(&self, rec: &HitRecord) -> Option<Box<dyn PDF>> {
+        let distribution = self.field?.distribution(rec.p, rec.normal)?;
+        Some(Box::new(distribution))
+    }
+
+    fn record(&self, rec: &HitRecord, direction: Vec3, incoming: Color, pdf: Float) {
+        if let Some(recorder) = self.recorder {
+            recorder.record(rec.p, direction, incoming.luminance() / pdf);
+        }
+    }
+}
@@ -0,0 +1,125 @@
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use rayon::prelude::*;
+
+use crate::{
+    camera::Camera,
+    color::Color,
+    hittable::Hittable,
+    lights::light::Light,
+    render_parameters::RenderParameters,
+    sampler::{random, with_primary_samples, PrimarySampleSpace},
+    Float,
+};
+
+use super::{
+    direct_lighting::RaySource,
+    film::Film,
+    rayon::{generate_ray, ray_color},
+};
+
+#[derive(Clone, Copy)]
+pub struct MetropolisParameters {
+    pub bootstrap_samples: i32,
+    pub chains: i32,
+    pub sigma: Float,
+    pub large_step_probability: Float,
+}
+
+pub fn render(
+    camera: &Camera,
+    world: &dyn Hittable,
+    importants: &dyn Hittable,
+    lights: &[&dyn Light],
+    mlt_params: MetropolisParameters,
+    render_params: RenderParameters,
+) -> Vec<Vec<Color>> {
+    let width = render_params.image_width;
+    let height = render_params.image_height;
+
+    // Maps the primary sample vector to an image position and a path traced through it.
+    let evaluate = |samples: PrimarySampleSpace| {
+        with_primary_samples(samples, || {
+            let x = ((random() * width as Float) as i32).min(width - 1);
+            let y = ((random() * height as Float) as i32).min(height - 1);
+            let mut color = ray_color(
+                &generate_ray(camera, (x, y)),
+                world,
+                importants,
+                lights,
+                render_params.max_depth,
+                render_params.background_color,
+                RaySource::Camera,
+            );
+            color.correct_nans();
+            (color, (x, y))
+        })
+    };
+    let new_samples = |seed: u64| {
+        PrimarySampleSpace::new(seed, mlt_params.sigma, mlt_params.large_step_probability)
+    };
+
+    let bootstrap_weights: Vec<Float> = (0..mlt_params.bootstrap_samples as u64)
+        .into_par_iter()
+        .map(|seed| evaluate(new_samples(seed)).0 .0.luminance().max(0.0))
+        .collect();
+    let mut bootstrap_cdf = Vec::with_capacity(bootstrap_weights.len());
+    let mut total_weight = 0.0;
+    for weight in &bootstrap_weights {
+        total_weight += weight;
+        bootstrap_cdf.push(total_weight);
+    }
+    let mut image = vec![vec![Color::new(0.0, 0.0, 0.0); width as usize]; height as usize];
+    if total_weight <= 0.0 {
+        return image;
+    }
+    let b = total_weight / bootstrap_weights.len() as Float;
+
+    let film = Film::new(width, height);
+    let total_mutations = render_params.num_samples as i64 * width as i64 * height as i64;
+    let chains = mlt_params.chains.max(1) as i64;
+    (0..chains).into_par_iter().for_each(|chain| {
+        let mut rng = StdRng::seed_from_u64(mlt_params.bootstrap_samples as u64 + chain as u64);
+        let target = rng.gen::<Float>() * total_weight;
+        let seed = bootstrap_cdf
+            .partition_point(|&c| c <= target)
+            .min(bootstrap_cdf.len() - 1);
+
+        let ((mut current, mut current_pixel), mut samples) = evaluate(new_samples(seed as u64));
+        let mutations = total_mutations / chains + i64::from(chain < total_mutations % chains);
+        for _ in 0..mutations {
+            samples.start_iteration();
+            let ((proposed, proposed_pixel), mutated) = evaluate(samples);
+            samples = mutated;
+
+            let current_luminance = current.luminance();
+            let proposed_luminance = proposed.luminance();
+            let accept = if current_luminance > 0.0 {
+                Float::min(1.0, proposed_luminance / current_luminance)
+            } else {
+                1.0
+            };
+
+            // Both states are splatted with their expected weights to reduce variance.
+            if proposed_luminance > 0.0 {
+                film.splat(proposed_pixel, proposed * (accept * b / proposed_luminance));
+            }
+            if current_luminance > 0.0 {
+                film.splat(
+                    current_pixel,
+                    current * ((1.0 - accept) * b / current_luminance),
+                );
+            }
+
+            if samples.uniform() < accept {
+                current = proposed;
+                current_pixel = proposed_pixel;
+                samples.accept();
+            } else {
+                samples.reject();
+            }
+        }
+    });
+
+    film.add_to(&mut image);
+    image
+}
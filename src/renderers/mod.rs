@@ -1 +1,123 @@
+pub mod ambient_occlusion;
+pub mod bidirectional;
+pub mod direct_lighting;
+pub mod emission;
+pub mod film;
+pub mod guided;
+pub mod metropolis;
+pub mod photon_mapping;
 pub mod rayon;
+pub mod reservoir;
+pub mod spectral;
+
+use std::{error::Error, fmt::Display};
+
+use crate::{
+    camera::Camera,
+    color::Color,
+    hittable::{Hittable, HittableList},
+    render_parameters::RenderParameters,
+    scene::Scene,
+};
+
+use self::{
+    ambient_occlusion::AmbientOcclusionParameters, guided::GuidingParameters,
+    metropolis::MetropolisParameters, photon_mapping::PhotonMappingParameters,
+    reservoir::ReservoirParameters,
+};
+
+#[derive(Clone, Copy)]
+pub enum RenderMode {
+    PathTracing,
+    AmbientOcclusion(AmbientOcclusionParameters),
+    DirectLighting,
+    // Bidirectional path tracing and photon mapping start paths on emissive geometry only, and
+    // fail with `RenderError::UnsupportedLights` on scenes with lights from `Scene::add_light`.
+    Bidirectional,
+    PhotonMapping(PhotonMappingParameters),
+    Metropolis(MetropolisParameters),
+    Guided(GuidingParameters),
+    Reservoir(ReservoirParameters),
+    Spectral,
+}
+
+#[derive(Debug)]
+pub enum RenderError {
+    // The mode can only start paths on emissive geometry, but the scene has lights from `Scene::add_light`.
+    UnsupportedLights(&'static str),
+}
+
+impl Display for RenderError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RenderError::UnsupportedLights(mode) => {
+                write!(
+                    f,
+                    "{mode} cannot trace paths from lights added with add_light"
+                )
+            }
+        }
+    }
+}
+
+impl Error for RenderError {}
+
+pub fn render(
+    mode: RenderMode,
+    camera: &Camera,
+    scene: &Scene,
+    render_params: RenderParameters,
+) -> Result<Vec<Vec<Color>>, RenderError> {
+    // Fog is found by the same intersection queries that find the objects it surrounds.
+    let fogged = scene
+        .fog()
+        .map(|fog| HittableList::new(vec![scene.world, fog]));
+    let world = fogged
+        .as_ref()
+        .map_or(scene.world, |fogged| fogged as &dyn Hittable);
+    let importants = scene.importants();
+    let lights = scene.lights();
+    let image = match mode {
+        RenderMode::PathTracing => rayon::render(camera, world, importants, lights, render_params),
+        RenderMode::AmbientOcclusion(ao_params) => {
+            ambient_occlusion::render(camera, world, ao_params, render_params)
+        }
+        RenderMode::DirectLighting => {
+            direct_lighting::render(camera, world, importants, lights, render_params)
+        }
+        RenderMode::Bidirectional => {
+            if !lights.is_empty() {
+                return Err(RenderError::UnsupportedLights("Bidirectional path tracing"));
+            }
+            bidirectional::render(camera, world, importants, render_params)
+        }
+        RenderMode::PhotonMapping(pm_params) => {
+            if !lights.is_empty() {
+                return Err(RenderError::UnsupportedLights("Photon mapping"));
+            }
+            photon_mapping::render(camera, world, importants, pm_params, render_params)
+        }
+        RenderMode::Metropolis(mlt_params) => {
+            metropolis::render(camera, world, importants, lights, mlt_params, render_params)
+        }
+        RenderMode::Guided(guiding_params) => guided::render(
+            camera,
+            world,
+            scene.world.bounding_box(),
+            importants,
+            lights,
+            guiding_params,
+            render_params,
+        ),
+        RenderMode::Reservoir(reservoir_params) => reservoir::render(
+            camera,
+            world,
+            importants,
+            lights,
+            reservoir_params,
+            render_params,
+        ),
+        RenderMode::Spectral => spectral::render(camera, world, importants, lights, render_params),
+    };
+    Ok(image)
+}
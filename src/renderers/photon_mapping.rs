@@ -0,0 +1,335 @@
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use rayon::prelude::*;
+
+use crate::{
+    camera::Camera,
+    color::Color,
+    hittable::{HitRecord, Hittable},
+    interval::Interval,
+    ray::Ray,
+    render_parameters::RenderParameters,
+    Float, Vec3, PI,
+};
+
+use super::{
+    direct_lighting::sample_light, emission::sample_emission, film::Film, rayon::generate_ray,
+};
+
+// Fraction of the photons found in a pass that is kept when shrinking the gather radius.
+const ALPHA: Float = 2.0 / 3.0;
+
+#[derive(Clone, Copy)]
+pub struct PhotonMappingParameters {
+    pub photons_per_iteration: i32,
+    pub initial_radius: Float,
+}
+
+struct VisiblePoint<'a> {
+    rec: HitRecord<'a>,
+    r_in: Ray,
+    color: Color,
+    beta: Color,
+}
+
+impl<'a> VisiblePoint<'a> {
+    fn f(&self, direction: Vec3) -> Color {
+        let cos_theta = self.rec.normal.dot(direction.normalize()).abs();
+        if cos_theta < 1.0e-8 {
+            return Color::new(0.0, 0.0, 0.0);
+        }
+        let scattered = Ray::new(self.rec.p, direction);
+        self.rec
+            .material
+            .scattering_color(&self.r_in, &self.rec, &scattered, self.color)
+            / cos_theta
+    }
+}
+
+#[derive(Clone, Copy)]
+struct PixelStatistics {
+    direct: Color,
+    tau: Color,
+    n: Float,
+    radius: Float,
+}
+
+// Visible points bucketed by a hashed uniform grid whose cells are as wide as the largest gather sphere.
+struct PhotonGrid {
+    min: Vec3,
+    cell_size: Float,
+    cells: Vec<Vec<u32>>,
+}
+
+impl PhotonGrid {
+    fn new(points: &[Option<VisiblePoint>], pixels: &[PixelStatistics]) -> Self {
+        let mut min = Vec3::splat(Float::MAX);
+        let mut max_radius: Float = 0.0;
+        for (vp, pixel) in points.iter().zip(pixels) {
+            if let Some(vp) = vp {
+                min = min.min(vp.rec.p - Vec3::splat(pixel.radius));
+                max_radius = max_radius.max(pixel.radius);
+            }
+        }
+
+        let mut grid = Self {
+            min,
+            cell_size: 2.0 * max_radius,
+            cells: vec![vec![]; points.len().max(1)],
+        };
+        for (index, (vp, pixel)) in points.iter().zip(pixels).enumerate() {
+            let Some(vp) = vp else {
+                continue;
+            };
+            let lo = grid.cell_of(vp.rec.p - Vec3::splat(pixel.radius));
+            let hi = grid.cell_of(vp.rec.p + Vec3::splat(pixel.radius));
+            for x in lo[0]..=hi[0] {
+                for y in lo[1]..=hi[1] {
+                    for z in lo[2]..=hi[2] {
+                        let bucket = grid.bucket([x, y, z]);
+                        grid.cells[bucket].push(index as u32);
+                    }
+                }
+            }
+        }
+        grid
+    }
+
+    fn cell_of(&self, p: Vec3) -> [i32; 3] {
+        let local = (p - self.min) / self.cell_size;
+        [local.x as i32, local.y as i32, local.z as i32]
+    }
+
+    fn bucket(&self, [x, y, z]: [i32; 3]) -> usize {
+        let hash = (x as u32).wrapping_mul(73856093)
+            ^ (y as u32).wrapping_mul(19349663)
+            ^ (z as u32).wrapping_mul(83492791);
+        hash as usize % self.cells.len()
+    }
+
+    fn candidates(&self, p: Vec3) -> &[u32] {
+        let local = (p - self.min) / self.cell_size;
+        if local.min_element() < 0.0 {
+            return &[];
+        }
+        &self.cells[self.bucket(self.cell_of(p))]
+    }
+}
+
+pub fn render(
+    camera: &Camera,
+    world: &dyn Hittable,
+    importants: &dyn Hittable,
+    pm_params: PhotonMappingParameters,
+    render_params: RenderParameters,
+) -> Vec<Vec<Color>> {
+    let width = render_params.image_width;
+    let height = render_params.image_height;
+    let mut pixels = vec![
+        PixelStatistics {
+            direct: Color::new(0.0, 0.0, 0.0),
+            tau: Color::new(0.0, 0.0, 0.0),
+            n: 0.0,
+            radius: pm_params.initial_radius,
+        };
+        (width * height) as usize
+    ];
+
+    for _ in 0..render_params.num_samples {
+        let camera_pass: Vec<(Color, Option<VisiblePoint>)> = (0..width * height)
+            .into_par_iter()
+            .map(|index| {
+                let ray = generate_ray(camera, (index % width, index / width));
+                trace_camera_path(&ray, world, importants, render_params)
+            })
+            .collect();
+        let (direct, visible_points): (Vec<Color>, Vec<Option<VisiblePoint>>) =
+            camera_pass.into_iter().unzip();
+
+        let grid = PhotonGrid::new(&visible_points, &pixels);
+        let flux = Film::new(width, height);
+        let counts: Vec<AtomicU32> = (0..width * height).map(|_| AtomicU32::new(0)).collect();
+
+        let photon_pass = PhotonPass {
+            world,
+            importants,
+            max_depth: render_params.max_depth,
+            width,
+            grid: &grid,
+            visible_points: &visible_points,
+            pixels: &pixels,
+            flux: &flux,
+            counts: &counts,
+        };
+        (0..pm_params.photons_per_iteration)
+            .into_par_iter()
+            .for_each(|_| photon_pass.trace_photon());
+
+        pixels
+            .par_iter_mut()
+            .enumerate()
+            .for_each(|(index, pixel)| {
+                let mut color = direct[index];
+                color.correct_nans();
+                pixel.direct += color;
+
+                let m = counts[index].load(Ordering::Relaxed) as Float;
+                let Some(vp) = &visible_points[index] else {
+                    return;
+                };
+                if m <= 0.0 {
+                    return;
+                }
+                let n = pixel.n + ALPHA * m;
+                let radius = pixel.radius * Float::sqrt(n / (pixel.n + m));
+                let phi = flux.get((index as i32 % width, index as i32 / width));
+                let shrink = (radius * radius) / (pixel.radius * pixel.radius);
+                pixel.tau = (pixel.tau + vp.beta * phi) * shrink;
+                pixel.n = n;
+                pixel.radius = radius;
+            });
+    }
+
+    // The image writer averages over `num_samples` iterations, so the photon estimate is scaled up to match.
+    let photons = pm_params.photons_per_iteration.max(1) as Float;
+    pixels
+        .chunks(width as usize)
+        .map(|row| {
+            row.iter()
+                .map(|pixel| {
+                    let area = PI * pixel.radius * pixel.radius;
+                    let mut color = pixel.direct + pixel.tau / (photons * area);
+                    color.correct_nans();
+                    color
+                })
+                .collect()
+        })
+        .collect()
+}
+
+// Follows specular bounces from the camera until a surface that can hold a visible point.
+fn trace_camera_path<'a>(
+    ray: &Ray,
+    world: &'a dyn Hittable,
+    importants: &dyn Hittable,
+    render_params: RenderParameters,
+) -> (Color, Option<VisiblePoint<'a>>) {
+    let mut ray = *ray;
+    let mut beta = Color::new(1.0, 1.0, 1.0);
+    let mut direct = Color::new(0.0, 0.0, 0.0);
+    for _ in 0..render_params.max_depth {
+        let Some(rec) = world.hit(
+            &ray,
+            Interval {
+                min: 0.001,
+                max: Float::MAX,
+            },
+        ) else {
+            return (direct + beta * render_params.background_color, None);
+        };
+
+        let Some(mat_hit_res) = rec.material.scatter(&ray, &rec) else {
+            return (direct + beta * rec.material.emit_color(&ray, &rec), None);
+        };
+
+        if mat_hit_res.pdf.is_none() {
+            beta = beta * mat_hit_res.color;
+            ray = mat_hit_res.ray;
+            continue;
+        }
+
+        direct += beta * sample_light(&ray, &rec, mat_hit_res.color, world, importants);
+        return (
+            direct,
+            Some(VisiblePoint {
+                rec,
+                r_in: ray,
+                color: mat_hit_res.color,
+                beta,
+            }),
+        );
+    }
+
+    (direct, None)
+}
+
+struct PhotonPass<'a> {
+    world: &'a dyn Hittable,
+    importants: &'a dyn Hittable,
+    max_depth: i32,
+    width: i32,
+    grid: &'a PhotonGrid,
+    visible_points: &'a [Option<VisiblePoint<'a>>],
+    pixels: &'a [PixelStatistics],
+    flux: &'a Film,
+    counts: &'a [AtomicU32],
+}
+
+impl<'a> PhotonPass<'a> {
+    fn trace_photon(&self) {
+        let Some(sample) = sample_emission(self.importants) else {
+            return;
+        };
+        let cos_theta = sample.normal.dot(sample.direction).abs();
+        let mut beta = sample.emitted * cos_theta / (sample.pdf_pos * sample.pdf_dir);
+        let mut ray = Ray::new(sample.p, sample.direction);
+
+        for depth in 0..self.max_depth {
+            let Some(rec) = self.world.hit(
+                &ray,
+                Interval {
+                    min: 0.001,
+                    max: Float::MAX,
+                },
+            ) else {
+                return;
+            };
+            let Some(mat_hit_res) = rec.material.scatter(&ray, &rec) else {
+                return;
+            };
+
+            let Some(mat_pdf) = mat_hit_res.pdf else {
+                beta = beta * mat_hit_res.color;
+                ray = mat_hit_res.ray;
+                continue;
+            };
+
+            // Direct illumination is already estimated by light sampling at the visible points.
+            if depth > 0 {
+                for &index in self.grid.candidates(rec.p) {
+                    let index = index as usize;
+                    let Some(vp) = &self.visible_points[index] else {
+                        continue;
+                    };
+                    let radius = self.pixels[index].radius;
+                    if (vp.rec.p - rec.p).length_squared() > radius * radius {
+                        continue;
+                    }
+                    self.flux.splat(
+                        (index as i32 % self.width, index as i32 / self.width),
+                        beta * vp.f(-ray.direction),
+                    );
+                    self.counts[index].fetch_add(1, Ordering::Relaxed);
+                }
+            }
+
+            let Some(direction) = mat_pdf.generate() else {
+                return;
+            };
+            let pdf = mat_pdf.value(&direction);
+            if pdf <= 0.0 {
+                return;
+            }
+            let scattered = Ray::new(rec.p, direction);
+            beta = beta
+                * rec
+                    .material
+                    .scattering_color(&ray, &rec, &scattered, mat_hit_res.color)
+                / pdf;
+            if beta.is_black() {
+                return;
+            }
+            ray = scattered;
+        }
+    }
+}
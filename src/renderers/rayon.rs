@@ -1,24 +1,52 @@
 use crate::{
     camera::Camera,
     color::Color,
-    hittable::Hittable,
+    hittable::{HitRecord, Hittable},
     interval::Interval,
+    lights::light::Light,
     pdf::{HittablePDF, MixturePDF, PDF},
     rand_vec3::random_vec_unit_disk,
     ray::Ray,
     render_parameters::RenderParameters,
+    sampler::random,
     Float, Vec3,
 };
 
-use rand::random;
 use rayon::prelude::*;
 
+use super::direct_lighting::{
+    escaped_light_as, sample_lights_as, AsColor, Convert, Radiance, RaySource,
+};
+
 pub fn render(
     camera: &Camera,
     world: &dyn Hittable,
     importants: &dyn Hittable,
+    lights: &[&dyn Light],
     render_params: RenderParameters,
 ) -> Vec<Vec<Color>> {
+    render_with(camera, render_params, |ray| {
+        ray_color(
+            ray,
+            world,
+            importants,
+            lights,
+            render_params.max_depth,
+            Color::new(0.0, 0.0, 0.0),
+            RaySource::Camera,
+        )
+        .clamp()
+    })
+}
+
+pub fn render_with<F>(
+    camera: &Camera,
+    render_params: RenderParameters,
+    ray_color: F,
+) -> Vec<Vec<Color>>
+where
+    F: Fn(&Ray) -> Color + Sync,
+{
     let input_row: Vec<(i32, i32)> = vec![(0, 0); render_params.image_width as usize];
     let mut image: Vec<Vec<(i32, i32)>> =
         vec![input_row.clone(); render_params.image_height as usize];
@@ -35,14 +63,7 @@ pub fn render(
                 .map(|(j, i)| {
                     let mut color = Color::new(0.0, 0.0, 0.0);
                     for _ in 0..render_params.num_samples {
-                        color += ray_color(
-                            &generate_ray(camera, (*i, *j)),
-                            world,
-                            importants,
-                            render_params.max_depth,
-                            Color::new(0.0, 0.0, 0.0),
-                        )
-                        .clamp();
+                        color += ray_color(&generate_ray(camera, (*i, *j)));
                     }
                     color.correct_nans();
                     color
@@ -54,57 +75,140 @@ pub fn render(
     return rendered_image;
 }
 
-fn ray_color(
+pub fn ray_color(
     ray: &Ray,
     world: &dyn Hittable,
     important_objs: &dyn Hittable,
+    lights: &[&dyn Light],
     depth: i32,
     background_color: Color,
+    source: RaySource,
 ) -> Color {
-    if depth <= 0 {
-        return Color::new(1.0, 1.0, 1.0);
+    PathTracer {
+        world,
+        important_objs,
+        lights,
+        background_color,
+        convert: &AsColor,
+        single_wavelength: &|light| light,
+        guide: None,
     }
-    if let Some(rec) = world.hit(
-        ray,
-        Interval {
-            min: 0.001,
-            max: Float::MAX,
-        },
-    ) {
-        if let Some(mat_hit_res) = rec.material.scatter(ray, &rec) {
-            if mat_hit_res.pdf.is_none() {
-                return mat_hit_res.color
-                    * ray_color(
-                        &mat_hit_res.ray,
-                        world,
-                        important_objs,
-                        depth - 1,
-                        background_color,
-                    );
+    .ray_color(ray, depth, source)
+}
+
+// Path tracing that carries light as `R`, into which `convert` turns every colour met.
+pub(crate) struct PathTracer<'a, R> {
+    pub world: &'a dyn Hittable,
+    pub important_objs: &'a dyn Hittable,
+    pub lights: &'a [&'a dyn Light],
+    pub background_color: Color,
+    pub convert: &'a dyn Convert<R>,
+    // Keeps only the light at the wavelength a ray carries, for light scattered by materials
+    // whose response depends on it.
+    pub single_wavelength: &'a (dyn Fn(R) -> R + Sync),
+    pub guide: Option<&'a dyn Guide<R>>,
+}
+
+// Learned knowledge of where light comes from, which a path tracer both samples and teaches.
+pub(crate) trait Guide<R>: Sync {
+    // Directions to mix with those of the material at `rec`, if anything was learned there.
+    fn distribution(&self, rec: &HitRecord) -> Option<Box<dyn PDF>>;
+    // Light `incoming` from `direction` reached `rec`, sampled with density `pdf`.
+    fn record(&self, rec: &HitRecord, direction: Vec3, incoming: R, pdf: Float);
+}
+
+impl<'a, R: Radiance> PathTracer<'a, R> {
+    pub(crate) fn ray_color(&self, ray: &Ray, depth: i32, source: RaySource) -> R {
+        let convert = self.convert;
+        if depth <= 0 {
+            return convert.light(Color::new(1.0, 1.0, 1.0));
+        }
+        let Some(rec) = self.world.hit(
+            ray,
+            Interval {
+                min: 0.001,
+                max: Float::MAX,
+            },
+        ) else {
+            return convert.light(self.background_color)
+                + escaped_light_as(ray, self.lights, source, convert);
+        };
+
+        let Some(mat_hit_res) = rec.material.scatter(ray, &rec) else {
+            return convert.light(rec.material.emit_color(ray, &rec));
+        };
+        // Emissive media both glow and scatter where a ray collides with them.
+        let emitted = convert.light(rec.material.emit_color(ray, &rec));
+        let dispersive = rec.material.is_dispersive(ray, &rec);
+        let scattered_light = |light: R| {
+            if dispersive {
+                (self.single_wavelength)(light)
+            } else {
+                light
             }
-            let mat_pdf = mat_hit_res.pdf.unwrap();
-            let pdf = HittablePDF::new(rec.p, important_objs);
-            let mix_pdf = MixturePDF::new(&pdf, &*mat_pdf);
-            let scattered = Ray::new(rec.p, mix_pdf.generate());
-            let pdf_value = mix_pdf.value(&scattered.direction);
-            let scattering_pdf = rec.material.scattering_pdf(ray, &rec, &scattered);
-
-            return mat_hit_res.color
-                * ray_color(
-                    &scattered,
-                    world,
-                    important_objs,
-                    depth - 1,
-                    background_color,
-                )
-                * scattering_pdf
-                / pdf_value;
+        };
+        // Light already kept at a single wavelength is not reduced again further along the path.
+        let single = PathTracer {
+            single_wavelength: &|light| light,
+            ..*self
+        };
+        let tracer = if dispersive { &single } else { self };
+        if mat_hit_res.pdf.is_none() {
+            let next = mat_hit_res.ray.with_wavelength(ray.wavelength);
+            return emitted
+                + scattered_light(
+                    convert.reflectance(mat_hit_res.color)
+                        * tracer.ray_color(&next, depth - 1, RaySource::Specular),
+                );
+        }
+
+        let mat_pdf = mat_hit_res.pdf.unwrap();
+        let guide_pdf = self.guide.and_then(|guide| guide.distribution(&rec));
+        let guided_pdf = guide_pdf
+            .as_ref()
+            .map(|guide_pdf| MixturePDF::new(&**guide_pdf, &*mat_pdf));
+        let surface_pdf: &dyn PDF = match &guided_pdf {
+            Some(guided_pdf) => guided_pdf,
+            None => &*mat_pdf,
+        };
+        let light_pdf = HittablePDF::new(rec.p, self.important_objs);
+        let mix_pdf = MixturePDF::new(&light_pdf, surface_pdf);
+        // Without anything to aim at, only the surface can pick directions.
+        let pdf: &dyn PDF = if self.important_objs.is_empty() {
+            surface_pdf
         } else {
-            return rec.material.emit_color(ray, &rec);
+            &mix_pdf
+        };
+        let direct = sample_lights_as(
+            ray,
+            &rec,
+            mat_hit_res.color,
+            self.world,
+            self.lights,
+            &|direction| pdf.value(direction),
+            convert,
+        );
+        // Keep the light samples when no direction was found or it cannot carry any light.
+        let Some(direction) = pdf.generate() else {
+            return emitted + scattered_light(direct);
+        };
+        let scattered = Ray::new(rec.p, direction).with_wavelength(ray.wavelength);
+        let pdf_value = pdf.value(&scattered.direction);
+        let scattering_color =
+            rec.material
+                .scattering_color(ray, &rec, &scattered, mat_hit_res.color);
+        if pdf_value <= 0.0 || scattering_color.is_black() {
+            return emitted + scattered_light(direct);
         }
-    }
 
-    return background_color;
+        let incoming = tracer.ray_color(&scattered, depth - 1, RaySource::Sampled(pdf_value));
+        if let Some(guide) = self.guide {
+            guide.record(&rec, scattered.direction, incoming, pdf_value);
+        }
+
+        emitted
+            + scattered_light(direct + convert.reflectance(scattering_color) * incoming / pdf_value)
+    }
 }
 
 pub fn generate_ray(camera: &Camera, (x, y): (i32, i32)) -> Ray {
@@ -126,8 +230,8 @@ pub fn generate_ray(camera: &Camera, (x, y): (i32, i32)) -> Ray {
 }
 
 fn pixel_sample_square(camera: &Camera) -> Vec3 {
-    let px: Float = -0.5 + random::<Float>();
-    let py: Float = -0.5 + random::<Float>();
+    let px: Float = -0.5 + random();
+    let py: Float = -0.5 + random();
 
     return (px * camera.pixel_delta_u) + (py * camera.pixel_delta_v);
 }
@@ -0,0 +1,354 @@
+use rayon::prelude::*;
+
+use crate::{
+    camera::Camera,
+    color::Color,
+    hittable::{HitRecord, Hittable},
+    interval::Interval,
+    lights::light::Light,
+    ray::Ray,
+    render_parameters::RenderParameters,
+    sampler::random,
+    Float, Vec3, PI,
+};
+
+use super::{
+    direct_lighting::{escaped_light, sample_lights, RaySource},
+    rayon::generate_ray,
+};
+
+// Light points are found again by intersecting within this distance of the end of a shadow ray.
+const LIGHT_EPSILON: Float = 1.0e-3;
+
+#[derive(Clone, Copy)]
+pub struct ReservoirParameters {
+    pub candidates: i32,
+    pub spatial_neighbors: i32,
+    pub spatial_radius: Float,
+}
+
+struct ShadingPoint<'a> {
+    rec: HitRecord<'a>,
+    r_in: Ray,
+    color: Color,
+    beta: Color,
+}
+
+impl<'a> ShadingPoint<'a> {
+    // Unshadowed contribution of the light point `y`, with the geometry term of the area measure.
+    fn unshadowed(&self, importants: &dyn Hittable, y: Vec3) -> Color {
+        let offset = y - self.rec.p;
+        let distance = offset.length();
+        let to_light = Ray::new(self.rec.p, offset / distance);
+        let Some(light_rec) = importants.hit(
+            &to_light,
+            Interval {
+                min: distance - LIGHT_EPSILON,
+                max: distance + LIGHT_EPSILON,
+            },
+        ) else {
+            return Color::new(0.0, 0.0, 0.0);
+        };
+        if light_rec.material.scatter(&to_light, &light_rec).is_some() {
+            return Color::new(0.0, 0.0, 0.0);
+        }
+
+        let cos_light = light_rec.normal.dot(to_light.direction).abs();
+        self.rec
+            .material
+            .scattering_color(&self.r_in, &self.rec, &to_light, self.color)
+            * light_rec.material.emit_color(&to_light, &light_rec)
+            * cos_light
+            / (distance * distance)
+    }
+
+    fn target(&self, importants: &dyn Hittable, y: Vec3) -> Float {
+        self.unshadowed(importants, y).luminance().max(0.0)
+    }
+
+    fn visible(&self, world: &dyn Hittable, y: Vec3) -> bool {
+        let offset = y - self.rec.p;
+        let distance = offset.length();
+        let to_light = Ray::new(self.rec.p, offset / distance);
+        world
+            .hit(
+                &to_light,
+                Interval {
+                    min: 0.001,
+                    max: distance - LIGHT_EPSILON,
+                },
+            )
+            .is_none()
+    }
+}
+
+// Weighted reservoir holding one light point chosen from a stream of candidates.
+#[derive(Clone, Copy, Default)]
+struct Reservoir {
+    sample: Option<Vec3>,
+    weight_sum: Float,
+    m: Float,
+    target: Float,
+    // Unbiased contribution weight of `sample`.
+    w: Float,
+}
+
+impl Reservoir {
+    fn update(&mut self, y: Vec3, weight: Float, target: Float, m: Float) {
+        self.m += m;
+        if weight <= 0.0 || !weight.is_finite() {
+            return;
+        }
+        self.weight_sum += weight;
+        if random() * self.weight_sum < weight {
+            self.sample = Some(y);
+            self.target = target;
+        }
+    }
+}
+
+struct Pixel<'a> {
+    emitted: Color,
+    point: Option<ShadingPoint<'a>>,
+}
+
+pub fn render(
+    camera: &Camera,
+    world: &dyn Hittable,
+    importants: &dyn Hittable,
+    lights: &[&dyn Light],
+    reservoir_params: ReservoirParameters,
+    render_params: RenderParameters,
+) -> Vec<Vec<Color>> {
+    let width = render_params.image_width;
+    let height = render_params.image_height;
+    let mut image = vec![Color::new(0.0, 0.0, 0.0); (width * height) as usize];
+
+    for _ in 0..render_params.num_samples {
+        let pixels: Vec<Pixel> = (0..width * height)
+            .into_par_iter()
+            .map(|index| {
+                let ray = generate_ray(camera, (index % width, index / width));
+                trace_camera_path(&ray, world, lights, render_params)
+            })
+            .collect();
+
+        let initial: Vec<Reservoir> = pixels
+            .par_iter()
+            .map(|pixel| {
+                pixel.point.as_ref().map_or(Reservoir::default(), |point| {
+                    initial_reservoir(point, importants, reservoir_params.candidates)
+                })
+            })
+            .collect();
+
+        let pass: Vec<Color> = (0..width * height)
+            .into_par_iter()
+            .map(|index| {
+                let pixel = &pixels[index as usize];
+                let Some(point) = &pixel.point else {
+                    return pixel.emitted;
+                };
+                // Lights outside the scene's geometry are sampled directly, without reuse.
+                let direct = point.beta
+                    * sample_lights(&point.r_in, &point.rec, point.color, world, lights, &|_| {
+                        0.0
+                    });
+                let neighbors = spatial_neighbors(
+                    &pixels,
+                    (index % width, index / width),
+                    (width, height),
+                    reservoir_params,
+                );
+                let reservoir =
+                    spatial_reuse(&pixels, &initial, index as usize, &neighbors, importants);
+                let Some(y) = reservoir.sample else {
+                    return pixel.emitted + direct;
+                };
+                if !point.visible(world, y) {
+                    return pixel.emitted + direct;
+                }
+                pixel.emitted + direct + point.beta * point.unshadowed(importants, y) * reservoir.w
+            })
+            .collect();
+
+        for (color, mut pass_color) in image.iter_mut().zip(pass) {
+            pass_color.correct_nans();
+            *color += pass_color;
+        }
+    }
+
+    image
+        .chunks(width as usize)
+        .map(|row| row.to_vec())
+        .collect()
+}
+
+// Follows specular bounces from the camera to the first surface that can be lit by a light sample.
+fn trace_camera_path<'a>(
+    ray: &Ray,
+    world: &'a dyn Hittable,
+    lights: &[&dyn Light],
+    render_params: RenderParameters,
+) -> Pixel<'a> {
+    let mut ray = *ray;
+    let mut beta = Color::new(1.0, 1.0, 1.0);
+    let mut source = RaySource::Camera;
+    for _ in 0..render_params.max_depth {
+        let Some(rec) = world.hit(
+            &ray,
+            Interval {
+                min: 0.001,
+                max: Float::MAX,
+            },
+        ) else {
+            return Pixel {
+                emitted: beta
+                    * (render_params.background_color + escaped_light(&ray, lights, source)),
+                point: None,
+            };
+        };
+
+        let Some(mat_hit_res) = rec.material.scatter(&ray, &rec) else {
+            return Pixel {
+                emitted: beta * rec.material.emit_color(&ray, &rec),
+                point: None,
+            };
+        };
+
+        if mat_hit_res.pdf.is_none() {
+            beta = beta * mat_hit_res.color;
+            ray = mat_hit_res.ray;
+            source = RaySource::Specular;
+            continue;
+        }
+
+        return Pixel {
+            emitted: Color::new(0.0, 0.0, 0.0),
+            point: Some(ShadingPoint {
+                rec,
+                r_in: ray,
+                color: mat_hit_res.color,
+                beta,
+            }),
+        };
+    }
+
+    Pixel {
+        emitted: Color::new(0.0, 0.0, 0.0),
+        point: None,
+    }
+}
+
+// Resampled importance sampling over light points drawn with `random_vector_to_surface`.
+fn initial_reservoir(
+    point: &ShadingPoint,
+    importants: &dyn Hittable,
+    candidates: i32,
+) -> Reservoir {
+    let mut reservoir = Reservoir::default();
+    let origin = point.rec.p;
+    for _ in 0..candidates.max(1) {
+        let direction = importants.random_vector_to_surface(&origin);
+        let pdf = importants.pdf_value(&origin, &direction);
+        let to_light = Ray::new(origin, direction);
+        let Some(light_rec) = importants.hit(
+            &to_light,
+            Interval {
+                min: 0.001,
+                max: Float::MAX,
+            },
+        ) else {
+            reservoir.m += 1.0;
+            continue;
+        };
+
+        // The source density is converted from solid angle to the area of the light.
+        let y = light_rec.p;
+        let offset = y - origin;
+        let cos_light = light_rec.normal.dot(offset.normalize()).abs();
+        let area_pdf = pdf * cos_light / offset.length_squared();
+        let target = point.target(importants, y);
+        reservoir.update(y, target / area_pdf, target, 1.0);
+    }
+
+    if reservoir.target > 0.0 {
+        reservoir.w = reservoir.weight_sum / (reservoir.m * reservoir.target);
+    }
+    reservoir
+}
+
+// Picks nearby pixels whose visible points lie on similar geometry.
+fn spatial_neighbors(
+    pixels: &[Pixel],
+    (x, y): (i32, i32),
+    (width, height): (i32, i32),
+    reservoir_params: ReservoirParameters,
+) -> Vec<usize> {
+    let Some(center) = &pixels[(y * width + x) as usize].point else {
+        return vec![];
+    };
+    let camera_distance = (center.rec.p - center.r_in.origin).length();
+
+    let mut neighbors = Vec::with_capacity(reservoir_params.spatial_neighbors as usize);
+    for _ in 0..reservoir_params.spatial_neighbors {
+        let radius = reservoir_params.spatial_radius * random().sqrt();
+        let angle = 2.0 * PI * random();
+        let nx = x + (radius * angle.cos()).round() as i32;
+        let ny = y + (radius * angle.sin()).round() as i32;
+        if nx < 0 || ny < 0 || nx >= width || ny >= height || (nx, ny) == (x, y) {
+            continue;
+        }
+        let index = (ny * width + nx) as usize;
+        let Some(neighbor) = &pixels[index].point else {
+            continue;
+        };
+        let neighbor_distance = (neighbor.rec.p - neighbor.r_in.origin).length();
+        if neighbor.rec.normal.dot(center.rec.normal) < 0.9
+            || (neighbor_distance - camera_distance).abs() > 0.1 * camera_distance
+        {
+            continue;
+        }
+        neighbors.push(index);
+    }
+    neighbors
+}
+
+// Combines the pixel's reservoir with its neighbors', counting only the ones that could have produced the result.
+fn spatial_reuse(
+    pixels: &[Pixel],
+    initial: &[Reservoir],
+    index: usize,
+    neighbors: &[usize],
+    importants: &dyn Hittable,
+) -> Reservoir {
+    let Some(point) = &pixels[index].point else {
+        return Reservoir::default();
+    };
+
+    let mut combined = Reservoir::default();
+    for &source in std::iter::once(&index).chain(neighbors) {
+        let reservoir = &initial[source];
+        let Some(y) = reservoir.sample else {
+            combined.m += reservoir.m;
+            continue;
+        };
+        let target = point.target(importants, y);
+        combined.update(y, target * reservoir.w * reservoir.m, target, reservoir.m);
+    }
+
+    let Some(y) = combined.sample else {
+        return combined;
+    };
+    let z: Float = std::iter::once(&index)
+        .chain(neighbors)
+        .filter_map(|&source| {
+            let source_point = pixels[source].point.as_ref()?;
+            (source_point.target(importants, y) > 0.0).then_some(initial[source].m)
+        })
+        .sum();
+    if z > 0.0 && combined.target > 0.0 {
+        combined.w = combined.weight_sum / (z * combined.target);
+    }
+    combined
+}
@@ -0,0 +1,217 @@
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+    ops::{Add, Div, Mul},
+    sync::Mutex,
+};
+
+use crate::{
+    camera::Camera,
+    color::Color,
+    hittable::Hittable,
+    lights::light::Light,
+    ray::Ray,
+    render_parameters::RenderParameters,
+    sampler::random,
+    spectrum::{cie_xyz, d65, xyz_to_rgb, RgbSpectrum, LAMBDA_MAX, LAMBDA_MIN},
+    Float, Vec3,
+};
+
+use rayon::prelude::*;
+
+use super::{
+    direct_lighting::{Convert, RaySource},
+    rayon::{generate_ray, PathTracer},
+};
+
+// Wavelengths carried along each path.
+const SAMPLES: usize = 4;
+// Colours each row of the image keeps the spectra of.
+const CACHED_SPECTRA: usize = 256;
+
+// Path tracing that follows wavelengths rather than colour channels. Each path carries a hero
+// wavelength and others evenly spaced from it, and every RGB colour it meets is turned into a
+// spectrum. The image is gathered in XYZ and turned into RGB at the end.
+pub fn render(
+    camera: &Camera,
+    world: &dyn Hittable,
+    importants: &dyn Hittable,
+    lights: &[&dyn Light],
+    render_params: RenderParameters,
+) -> Vec<Vec<Color>> {
+    let steps = ((LAMBDA_MAX - LAMBDA_MIN) / 5.0) as usize;
+    let white = (0..=steps).fold(Vec3::ZERO, |white, i| {
+        let lambda = LAMBDA_MIN + 5.0 * i as Float;
+        white + cie_xyz(lambda) * d65(lambda) * 5.0
+    });
+    let white = xyz_to_rgb(white);
+
+    (0..render_params.image_height)
+        .into_par_iter()
+        .map(|j| {
+            let spectra = SpectrumCache::new();
+            (0..render_params.image_width)
+                .map(|i| {
+                    let mut xyz = Vec3::ZERO;
+                    for _ in 0..render_params.num_samples {
+                        xyz += sample_xyz(&generate_ray(camera, (i, j)), |ray, lambdas| {
+                            PathTracer {
+                                world,
+                                important_objs: importants,
+                                lights,
+                                background_color: Color::new(0.0, 0.0, 0.0),
+                                convert: &Upsampler {
+                                    lambdas,
+                                    spectra: &spectra,
+                                },
+                                single_wavelength: &|light| light.hero_only(),
+                                guide: None,
+                            }
+                            .ray_color(
+                                ray,
+                                render_params.max_depth,
+                                RaySource::Camera,
+                            )
+                        });
+                    }
+                    let rgb = xyz_to_rgb(xyz);
+                    let mut color = Color::new(
+                        rgb.r() / white.r(),
+                        rgb.g() / white.g(),
+                        rgb.b() / white.b(),
+                    );
+                    color.correct_nans();
+                    color
+                })
+                .collect()
+        })
+        .collect()
+}
+
+// The colour in XYZ of the light `ray_color` brings along `ray` at freshly drawn wavelengths.
+fn sample_xyz<F>(ray: &Ray, ray_color: F) -> Vec3
+where
+    F: Fn(&Ray, &Wavelengths) -> SampledSpectrum,
+{
+    let lambdas = Wavelengths::sample();
+    let radiance = ray_color(&ray.with_wavelength(Some(lambdas.0[0])), &lambdas);
+    // Samples are clamped like colour channels are when path tracing in RGB, scaled down to the
+    // brightest that light no brighter than white brings, which is what the hero wavelength
+    // carries alone. Scaling every wavelength alike keeps the hue.
+    let brightest = (0..SAMPLES).fold(0.0, |brightest: Float, i| {
+        brightest.max(radiance.0[i] / d65(lambdas.0[i]))
+    });
+    let radiance = if brightest > SAMPLES as Float {
+        radiance * (SAMPLES as Float / brightest)
+    } else {
+        radiance
+    };
+    let range = LAMBDA_MAX - LAMBDA_MIN;
+    (0..SAMPLES).fold(Vec3::ZERO, |xyz, i| {
+        xyz + cie_xyz(lambdas.0[i]) * radiance.0[i] * range
+    }) / SAMPLES as Float
+}
+
+// A hero wavelength drawn uniformly over the visible range and the rest spaced evenly after it,
+// wrapping around.
+struct Wavelengths([Float; SAMPLES]);
+
+impl Wavelengths {
+    fn sample() -> Self {
+        let range = LAMBDA_MAX - LAMBDA_MIN;
+        let hero = random() * range;
+        Self(std::array::from_fn(|i| {
+            LAMBDA_MIN + (hero + range * i as Float / SAMPLES as Float) % range
+        }))
+    }
+}
+
+// Colours turned into spectra at the wavelengths of a path. Light is that of D65 tinted by the
+// spectrum of its colour.
+struct Upsampler<'a> {
+    lambdas: &'a Wavelengths,
+    spectra: &'a SpectrumCache,
+}
+
+impl<'a> Convert<SampledSpectrum> for Upsampler<'a> {
+    fn reflectance(&self, color: Color) -> SampledSpectrum {
+        if color.is_black() {
+            return SampledSpectrum::default();
+        }
+        let spectrum = self.spectra.get(color);
+        SampledSpectrum(self.lambdas.0.map(|lambda| spectrum.value(lambda)))
+    }
+
+    fn light(&self, color: Color) -> SampledSpectrum {
+        self.reflectance(color) * SampledSpectrum(self.lambdas.0.map(d65))
+    }
+}
+
+// Spectra of the colours met most recently, as the same few colours are met over and over.
+struct SpectrumCache(Mutex<Vec<Option<CachedSpectrum>>>);
+
+// A colour, by the bits of its channels, and its spectrum.
+type CachedSpectrum = ([u64; 3], RgbSpectrum);
+
+impl SpectrumCache {
+    fn new() -> Self {
+        Self(Mutex::new(vec![None; CACHED_SPECTRA]))
+    }
+
+    fn get(&self, color: Color) -> RgbSpectrum {
+        let key = [color.r(), color.g(), color.b()].map(|c| (c as f64).to_bits());
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        let slot = hasher.finish() as usize % CACHED_SPECTRA;
+        let mut spectra = self.0.lock().unwrap();
+        match spectra[slot] {
+            Some((cached, spectrum)) if cached == key => spectrum,
+            _ => {
+                let spectrum = RgbSpectrum::new(color);
+                spectra[slot] = Some((key, spectrum));
+                spectrum
+            }
+        }
+    }
+}
+
+// Values of a spectrum at the wavelengths a path carries.
+#[derive(Clone, Copy, Default)]
+struct SampledSpectrum([Float; SAMPLES]);
+
+impl SampledSpectrum {
+    // The value at the hero wavelength alone, weighted to stand for all of them.
+    fn hero_only(self) -> Self {
+        let mut hero = Self::default();
+        hero.0[0] = self.0[0] * SAMPLES as Float;
+        hero
+    }
+}
+
+impl Add for SampledSpectrum {
+    type Output = SampledSpectrum;
+    fn add(self, rhs: Self) -> Self::Output {
+        SampledSpectrum(std::array::from_fn(|i| self.0[i] + rhs.0[i]))
+    }
+}
+
+impl Mul for SampledSpectrum {
+    type Output = SampledSpectrum;
+    fn mul(self, rhs: Self) -> Self::Output {
+        SampledSpectrum(std::array::from_fn(|i| self.0[i] * rhs.0[i]))
+    }
+}
+
+impl Mul<Float> for SampledSpectrum {
+    type Output = SampledSpectrum;
+    fn mul(self, rhs: Float) -> Self::Output {
+        SampledSpectrum(self.0.map(|v| v * rhs))
+    }
+}
+
+impl Div<Float> for SampledSpectrum {
+    type Output = SampledSpectrum;
+    fn div(self, rhs: Float) -> Self::Output {
+        SampledSpectrum(self.0.map(|v| v / rhs))
+    }
+}
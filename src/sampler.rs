@@ -0,0 +1,125 @@
+use std::cell::RefCell;
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::{Float, PI};
+
+thread_local! {
+    static PRIMARY_SAMPLES: RefCell<Option<PrimarySampleSpace>> = const { RefCell::new(None) };
+}
+
+// Uniform random number in [0, 1) used by everything that samples a light path.
+pub fn random() -> Float {
+    PRIMARY_SAMPLES.with(|samples| match samples.borrow_mut().as_mut() {
+        Some(samples) => samples.next(),
+        None => rand::random(),
+    })
+}
+
+// Runs `f` with every call to `random` on this thread answered from `samples`.
+pub fn with_primary_samples<R>(
+    samples: PrimarySampleSpace,
+    f: impl FnOnce() -> R,
+) -> (R, PrimarySampleSpace) {
+    PRIMARY_SAMPLES.with(|s| *s.borrow_mut() = Some(samples));
+    let result = f();
+    let samples = PRIMARY_SAMPLES
+        .with(|s| s.borrow_mut().take())
+        .expect("Primary samples should still be installed");
+    (result, samples)
+}
+
+#[derive(Clone, Copy, Default)]
+struct PrimarySample {
+    value: Float,
+    last_modification: i64,
+    value_backup: Float,
+    modification_backup: i64,
+}
+
+// Lazily mutated vector of uniform numbers (Kelemen et al.), as used by primary sample space MLT.
+pub struct PrimarySampleSpace {
+    samples: Vec<PrimarySample>,
+    sample_index: usize,
+    current_iteration: i64,
+    large_step: bool,
+    last_large_step_iteration: i64,
+    sigma: Float,
+    large_step_probability: Float,
+    rng: StdRng,
+}
+
+impl PrimarySampleSpace {
+    pub fn new(seed: u64, sigma: Float, large_step_probability: Float) -> Self {
+        Self {
+            samples: vec![],
+            sample_index: 0,
+            current_iteration: 0,
+            large_step: true,
+            last_large_step_iteration: 0,
+            sigma,
+            large_step_probability,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    pub fn start_iteration(&mut self) {
+        self.current_iteration += 1;
+        self.large_step = self.rng.gen::<Float>() < self.large_step_probability;
+        self.sample_index = 0;
+    }
+
+    pub fn accept(&mut self) {
+        if self.large_step {
+            self.last_large_step_iteration = self.current_iteration;
+        }
+    }
+
+    pub fn reject(&mut self) {
+        for sample in self.samples.iter_mut() {
+            if sample.last_modification == self.current_iteration {
+                sample.value = sample.value_backup;
+                sample.last_modification = sample.modification_backup;
+            }
+        }
+        self.current_iteration -= 1;
+    }
+
+    pub fn uniform(&mut self) -> Float {
+        self.rng.gen()
+    }
+
+    fn next(&mut self) -> Float {
+        let index = self.sample_index;
+        self.sample_index += 1;
+        if index >= self.samples.len() {
+            self.samples.resize(index + 1, PrimarySample::default());
+        }
+
+        let sample = &mut self.samples[index];
+        // Catch up on a large step that happened while this sample was not being used.
+        if sample.last_modification < self.last_large_step_iteration {
+            sample.value = self.rng.gen();
+            sample.last_modification = self.last_large_step_iteration;
+        }
+
+        sample.value_backup = sample.value;
+        sample.modification_backup = sample.last_modification;
+        if self.large_step {
+            sample.value = self.rng.gen();
+        } else {
+            let small_steps = (self.current_iteration - sample.last_modification) as Float;
+            let u1: Float = self.rng.gen();
+            let u2: Float = self.rng.gen();
+            let normal = Float::sqrt(-2.0 * Float::ln(1.0 - u1)) * Float::cos(2.0 * PI * u2);
+            sample.value += normal * self.sigma * small_steps.sqrt();
+            sample.value -= sample.value.floor();
+            if sample.value >= 1.0 {
+                sample.value = 0.0;
+            }
+        }
+        sample.last_modification = self.current_iteration;
+
+        sample.value
+    }
+}
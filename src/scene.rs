@@ -0,0 +1,72 @@
+use crate::{
+    hittable::Hittable, light_list::LightList, light_tree::LightTree, lights::light::Light,
+    medium::ConstantMedium,
+};
+
+// The geometry to render together with the emitters that are importance sampled.
+pub struct Scene<'a> {
+    pub world: &'a dyn Hittable,
+    discovered: Box<dyn Hittable + 'a>,
+    importants: Option<&'a dyn Hittable>,
+    lights: Vec<&'a dyn Light>,
+    fog: Option<&'a ConstantMedium<'a>>,
+}
+
+impl<'a> Scene<'a> {
+    // Samples every object in `world` with an emissive material, weighted by its power.
+    pub fn new(world: &'a dyn Hittable) -> Self {
+        Self {
+            world,
+            discovered: Box::new(LightList::new(world.emitters())),
+            importants: None,
+            lights: vec![],
+            fog: None,
+        }
+    }
+
+    // Samples the emitters in `world` through a light tree, which favours those close to and
+    // facing each shading point. Worth it for scenes with many lights.
+    pub fn with_light_tree(world: &'a dyn Hittable) -> Self {
+        Self {
+            world,
+            discovered: Box::new(LightTree::new(world.emitters())),
+            importants: None,
+            lights: vec![],
+            fog: None,
+        }
+    }
+
+    // Samples `importants` instead of the emitters found in `world`.
+    pub fn with_importants(world: &'a dyn Hittable, importants: &'a dyn Hittable) -> Self {
+        Self {
+            world,
+            discovered: Box::new(LightList::new(vec![])),
+            importants: Some(importants),
+            lights: vec![],
+            fog: None,
+        }
+    }
+
+    pub fn importants(&self) -> &dyn Hittable {
+        self.importants.unwrap_or(&*self.discovered)
+    }
+
+    // Lights without geometry, such as point lights and environments. Bidirectional and photon
+    // mapping renders do not support them.
+    pub fn add_light(&mut self, light: &'a dyn Light) {
+        self.lights.push(light);
+    }
+
+    pub fn lights(&self) -> &[&'a dyn Light] {
+        &self.lights
+    }
+
+    // Fills the space between objects with `fog`.
+    pub fn set_fog(&mut self, fog: &'a ConstantMedium<'a>) {
+        self.fog = Some(fog);
+    }
+
+    pub fn fog(&self) -> Option<&'a ConstantMedium<'a>> {
+        self.fog
+    }
+}
@@ -0,0 +1,401 @@
+use std::sync::OnceLock;
+
+use crate::{color::Color, Float, Vec3};
+
+pub const LAMBDA_MIN: Float = 360.0;
+pub const LAMBDA_MAX: Float = 830.0;
+// Lumens per watt of a source whose luminance is one.
+pub const LUMINOUS_EFFICACY: Float = 683.0;
+
+// Wyman, Sloan and Shirley's piecewise Gaussian fit of the CIE 1931 colour matching functions.
+pub fn cie_xyz(lambda: Float) -> Vec3 {
+    let g = |mu: Float, sigma_below: Float, sigma_above: Float| {
+        let sigma = if lambda < mu {
+            sigma_below
+        } else {
+            sigma_above
+        };
+        let t = (lambda - mu) / sigma;
+        (-0.5 * t * t).exp()
+    };
+    Vec3::new(
+        1.056 * g(599.8, 37.9, 31.0) + 0.362 * g(442.0, 16.0, 26.7) - 0.065 * g(501.1, 20.4, 26.2),
+        0.821 * g(568.8, 46.9, 40.5) + 0.286 * g(530.9, 16.3, 31.1),
+        1.217 * g(437.0, 11.8, 36.0) + 0.681 * g(459.0, 26.0, 13.8),
+    )
+}
+
+// Linear sRGB with a D65 white point.
+pub fn xyz_to_rgb(xyz: Vec3) -> Color {
+    let rgb = xyz_to_linear_rgb(xyz).max(Vec3::ZERO);
+    Color::new(rgb.x, rgb.y, rgb.z)
+}
+
+// `xyz_to_rgb` keeping the negative values of colours outside the gamut.
+fn xyz_to_linear_rgb(xyz: Vec3) -> Vec3 {
+    Vec3::new(
+        3.2406 * xyz.x - 1.5372 * xyz.y - 0.4986 * xyz.z,
+        -0.9689 * xyz.x + 1.8758 * xyz.y + 0.0415 * xyz.z,
+        0.0557 * xyz.x - 0.2040 * xyz.y + 1.0570 * xyz.z,
+    )
+}
+
+// Planck's law up to a constant factor, for `lambda` in nanometres.
+pub fn planck(lambda: Float, kelvin: Float) -> Float {
+    let micrometres = lambda * 1.0e-3;
+    let exponent = 14387.77 / (micrometres * kelvin);
+    1.0 / (micrometres.powi(5) * (exponent.exp() - 1.0))
+}
+
+// Colour of a black body at `kelvin` in XYZ, up to the constant factor of `planck`.
+pub fn blackbody_xyz(kelvin: Float) -> Vec3 {
+    if kelvin <= 0.0 {
+        return Vec3::ZERO;
+    }
+    let steps = ((LAMBDA_MAX - LAMBDA_MIN) / 5.0) as usize;
+    (0..=steps)
+        .map(|i| {
+            let lambda = LAMBDA_MIN + 5.0 * i as Float;
+            cie_xyz(lambda) * planck(lambda, kelvin)
+        })
+        .fold(Vec3::ZERO, |sum, xyz| sum + xyz)
+}
+
+// Relative spectral power of CIE standard illuminant D65, the white of sRGB, every 10 nm from
+// `LAMBDA_MIN` to `LAMBDA_MAX`.
+const D65: [Float; 48] = [
+    46.6383, 52.0891, 49.9755, 54.6482, 82.7549, 91.486, 93.4318, 86.6823, 104.865, 117.008,
+    117.812, 114.861, 115.923, 108.811, 109.354, 107.802, 104.79, 107.689, 104.405, 104.046, 100.0,
+    96.3342, 95.788, 88.6856, 90.0062, 89.5991, 87.6987, 83.2886, 83.6992, 80.0268, 80.2146,
+    82.2778, 78.2842, 69.7213, 71.6091, 74.349, 61.604, 69.8856, 75.087, 63.5927, 46.4182, 66.8054,
+    63.3828, 64.304, 59.4519, 51.959, 57.4406, 60.3125,
+];
+
+// Power of D65 at `lambda`, relative to that at 560 nm.
+pub fn d65(lambda: Float) -> Float {
+    let x = ((lambda - LAMBDA_MIN) / 10.0).clamp(0.0, (D65.len() - 1) as Float);
+    let i = (x as usize).min(D65.len() - 2);
+    let t = x - i as Float;
+    (D65[i] * (1.0 - t) + D65[i + 1] * t) / 100.0
+}
+
+// The colour of a black body at `kelvin`, scaled to a luminance of one.
+pub fn blackbody(kelvin: Float) -> Color {
+    let xyz = blackbody_xyz(kelvin);
+    if xyz.y <= 0.0 {
+        return Color::new(0.0, 0.0, 0.0);
+    }
+    let rgb = xyz_to_rgb(xyz / xyz.y);
+    rgb / rgb.luminance()
+}
+
+// A smooth spectrum reproducing an RGB colour as a reflectance lit by D65, following Jakob and
+// Hanika's sigmoid of a quadratic polynomial. Colours brighter than one are scaled down to fit.
+// Light of an RGB colour has this spectrum times that of D65.
+#[derive(Clone, Copy)]
+pub struct RgbSpectrum {
+    coefficients: [Float; 3],
+    scale: Float,
+}
+
+impl RgbSpectrum {
+    pub fn new(rgb: Color) -> Self {
+        let brightest = rgb.r().max(rgb.g()).max(rgb.b());
+        if brightest <= 0.0 {
+            return Self {
+                coefficients: [0.0; 3],
+                scale: 0.0,
+            };
+        }
+        // Greys are flat, which a sigmoid of zero gives at half the value.
+        if rgb.r() == rgb.g() && rgb.g() == rgb.b() {
+            return Self {
+                coefficients: [0.0; 3],
+                scale: 2.0 * brightest,
+            };
+        }
+        if brightest <= 1.0 {
+            return Self {
+                coefficients: SpectrumTable::get().coefficients(rgb),
+                scale: 1.0,
+            };
+        }
+        let scale = 2.0 * brightest;
+        Self {
+            coefficients: SpectrumTable::get().coefficients(rgb / scale),
+            scale,
+        }
+    }
+
+    pub fn value(&self, lambda: Float) -> Float {
+        if self.scale == 0.0 {
+            return 0.0;
+        }
+        let t = (lambda - LAMBDA_MIN) / (LAMBDA_MAX - LAMBDA_MIN);
+        let [a, b, c] = self.coefficients;
+        self.scale * sigmoid((a * t + b) * t + c)
+    }
+}
+
+fn sigmoid(x: Float) -> Float {
+    if x.is_infinite() {
+        return if x > 0.0 { 1.0 } else { 0.0 };
+    }
+    0.5 + x / (2.0 * (1.0 + x * x).sqrt())
+}
+
+// Polynomial coefficients fitted over a grid of colours, indexed by the brightest channel, the
+// brightest value and the other two channels relative to it. The fit is made by the tests below
+// and shipped in `rgb_spectrum.bin`, as little-endian `f32`s.
+struct SpectrumTable {
+    z_nodes: Vec<Float>,
+    coefficients: Vec<[Float; 3]>,
+}
+
+impl SpectrumTable {
+    const RESOLUTION: usize = 32;
+
+    fn get() -> &'static Self {
+        static TABLE: OnceLock<SpectrumTable> = OnceLock::new();
+        TABLE.get_or_init(|| {
+            let bytes = include_bytes!("rgb_spectrum.bin");
+            let values: Vec<Float> = bytes
+                .chunks_exact(4)
+                .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]) as Float)
+                .collect();
+            let n = Self::RESOLUTION;
+            assert_eq!(values.len(), 3 * n * n * n * 3);
+            Self {
+                z_nodes: Self::z_nodes(),
+                coefficients: values.chunks_exact(3).map(|c| [c[0], c[1], c[2]]).collect(),
+            }
+        })
+    }
+
+    // Brightnesses the table is fitted at, denser towards black and full brightness, where the
+    // coefficients change quickest.
+    fn z_nodes() -> Vec<Float> {
+        let n = Self::RESOLUTION;
+        let smoothstep = |x: Float| x * x * (3.0 - 2.0 * x);
+        (0..n)
+            .map(|i| smoothstep(smoothstep(i as Float / (n - 1) as Float)))
+            .collect()
+    }
+
+    fn index(channel: usize, z: usize, y: usize, x: usize) -> usize {
+        let n = Self::RESOLUTION;
+        ((channel * n + z) * n + y) * n + x
+    }
+
+    fn coefficients(&self, rgb: Color) -> [Float; 3] {
+        let rgb = [rgb.r(), rgb.g(), rgb.b()];
+        let channel = (0..3).fold(0, |m, i| if rgb[i] > rgb[m] { i } else { m });
+        let z = rgb[channel];
+        let n = Self::RESOLUTION;
+        let scale = (n - 1) as Float / z;
+        let x = (rgb[(channel + 1) % 3] * scale).clamp(0.0, (n - 1) as Float);
+        let y = (rgb[(channel + 2) % 3] * scale).clamp(0.0, (n - 1) as Float);
+        let zi = self
+            .z_nodes
+            .partition_point(|&node| node <= z)
+            .clamp(1, n - 1)
+            - 1;
+        let xi = (x as usize).min(n - 2);
+        let yi = (y as usize).min(n - 2);
+        let (dx, dy) = (x - xi as Float, y - yi as Float);
+        let dz =
+            ((z - self.z_nodes[zi]) / (self.z_nodes[zi + 1] - self.z_nodes[zi])).clamp(0.0, 1.0);
+
+        let mut c = [0.0; 3];
+        for (corner, weight) in [
+            ((0, 0, 0), (1.0 - dz) * (1.0 - dy) * (1.0 - dx)),
+            ((0, 0, 1), (1.0 - dz) * (1.0 - dy) * dx),
+            ((0, 1, 0), (1.0 - dz) * dy * (1.0 - dx)),
+            ((0, 1, 1), (1.0 - dz) * dy * dx),
+            ((1, 0, 0), dz * (1.0 - dy) * (1.0 - dx)),
+            ((1, 0, 1), dz * (1.0 - dy) * dx),
+            ((1, 1, 0), dz * dy * (1.0 - dx)),
+            ((1, 1, 1), dz * dy * dx),
+        ] {
+            let (cz, cy, cx) = corner;
+            let fitted = self.coefficients[Self::index(channel, zi + cz, yi + cy, xi + cx)];
+            for k in 0..3 {
+                c[k] += weight * fitted[k];
+            }
+        }
+        c
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glam::{DMat3, DVec3};
+
+    // The colour of `spectrum` as a reflectance lit by D65, relative to a white one.
+    fn reflected(spectrum: &RgbSpectrum) -> Color {
+        let (mut xyz, mut white) = (Vec3::ZERO, Vec3::ZERO);
+        for i in 0..=470 {
+            let lambda = LAMBDA_MIN + i as Float;
+            xyz += cie_xyz(lambda) * d65(lambda) * spectrum.value(lambda);
+            white += cie_xyz(lambda) * d65(lambda);
+        }
+        let (rgb, white) = (xyz_to_rgb(xyz), xyz_to_rgb(white));
+        Color::new(
+            rgb.r() / white.r(),
+            rgb.g() / white.g(),
+            rgb.b() / white.b(),
+        )
+    }
+
+    fn error(a: Color, b: Color) -> Float {
+        let error = |a: Float, b: Float| (a - b).abs();
+        error(a.r(), b.r())
+            .max(error(a.g(), b.g()))
+            .max(error(a.b(), b.b()))
+    }
+
+    #[test]
+    fn spectra_reproduce_their_colours() {
+        let steps = 12;
+        let mut total = 0.0;
+        for r in 0..=steps {
+            for g in 0..=steps {
+                for b in 0..=steps {
+                    let step = |i: i32| i as Float / steps as Float;
+                    let rgb = Color::new(step(r), step(g), step(b));
+                    let error = error(reflected(&RgbSpectrum::new(rgb)), rgb);
+                    assert!(error < 0.05, "{rgb:?} is off by {error}");
+                    total += error;
+                }
+            }
+        }
+        let count = (steps + 1) * (steps + 1) * (steps + 1);
+        assert!(total / count as Float <= 0.005);
+    }
+
+    #[test]
+    fn bright_colours_are_scaled() {
+        let rgb = Color::new(6.0, 3.0, 1.5);
+        let error = error(reflected(&RgbSpectrum::new(rgb)), rgb);
+        assert!(error < 0.05 * 6.0);
+    }
+
+    // Refits the table shipped in `rgb_spectrum.bin`, run with `--ignored` after changing it.
+    #[test]
+    #[ignore]
+    fn write_table() {
+        let table = fit();
+        let bytes: Vec<u8> = table
+            .iter()
+            .flat_map(|c| c.iter().flat_map(|v| (*v as f32).to_le_bytes()))
+            .collect();
+        std::fs::write(
+            concat!(env!("CARGO_MANIFEST_DIR"), "/src/rgb_spectrum.bin"),
+            bytes,
+        )
+        .unwrap();
+    }
+
+    fn fit() -> Vec<[f64; 3]> {
+        let n = SpectrumTable::RESOLUTION;
+        let z_nodes = SpectrumTable::z_nodes();
+
+        // Each wavelength's share of linear RGB under D65, normalised so a flat spectrum of one
+        // is white.
+        let step = 5.0;
+        let steps = ((LAMBDA_MAX - LAMBDA_MIN) / step) as usize;
+        let samples: Vec<(f64, DVec3)> = (0..=steps)
+            .map(|i| {
+                let lambda = LAMBDA_MIN + step * i as Float;
+                let t = (lambda - LAMBDA_MIN) / (LAMBDA_MAX - LAMBDA_MIN);
+                let rgb = xyz_to_linear_rgb(cie_xyz(lambda) * d65(lambda));
+                (
+                    t as f64,
+                    DVec3::new(rgb.x as f64, rgb.y as f64, rgb.z as f64),
+                )
+            })
+            .collect();
+        let white = samples
+            .iter()
+            .fold(DVec3::ZERO, |white, (_, rgb)| white + *rgb);
+        let samples: Vec<(f64, DVec3)> = samples
+            .into_iter()
+            .map(|(t, rgb)| (t, rgb / white))
+            .collect();
+
+        let mut table = vec![[0.0; 3]; 3 * n * n * n];
+        let start = n / 5;
+        for channel in 0..3 {
+            for y in 0..n {
+                for x in 0..n {
+                    // Neighbouring brightnesses start from each other's fit.
+                    let mut c = [0.0; 3];
+                    for z in (start..n).chain((0..start).rev()) {
+                        if z + 1 == start {
+                            c = table[SpectrumTable::index(channel, start, y, x)];
+                        }
+                        let brightness = z_nodes[z] as f64;
+                        let mut target = [0.0; 3];
+                        target[channel] = brightness;
+                        target[(channel + 1) % 3] = x as f64 / (n - 1) as f64 * brightness;
+                        target[(channel + 2) % 3] = y as f64 / (n - 1) as f64 * brightness;
+                        c = gauss_newton(&samples, DVec3::from(target), c);
+                        table[SpectrumTable::index(channel, z, y, x)] = c;
+                    }
+                }
+            }
+        }
+        table
+    }
+
+    // Refines the coefficients `c` until their spectrum's colour matches `target`, or comes as
+    // close as it can for colours no spectrum between zero and one reproduces.
+    fn gauss_newton(samples: &[(f64, DVec3)], target: DVec3, mut c: [f64; 3]) -> [f64; 3] {
+        let sigmoid = |x: f64| 0.5 + x / (2.0 * (1.0 + x * x).sqrt());
+        let residual = |c: [f64; 3]| {
+            samples.iter().fold(-target, |residual, (t, rgb)| {
+                residual + *rgb * sigmoid((c[0] * t + c[1]) * t + c[2])
+            })
+        };
+        let mut error = residual(c);
+        for _ in 0..30 {
+            if error.length() < 1.0e-6 {
+                break;
+            }
+            let jacobian = samples.iter().fold(DMat3::ZERO, |jacobian, (t, rgb)| {
+                let x = (c[0] * t + c[1]) * t + c[2];
+                let slope = 0.5 / (1.0 + x * x).powf(1.5);
+                jacobian
+                    + DMat3::from_cols(*rgb * (slope * t * t), *rgb * (slope * t), *rgb * slope)
+            });
+            if jacobian.determinant().abs() < 1.0e-15 {
+                break;
+            }
+            let delta = jacobian.inverse() * error;
+            // Halve steps that overshoot.
+            let mut step = 1.0;
+            let improved = loop {
+                let next = [
+                    c[0] - step * delta.x,
+                    c[1] - step * delta.y,
+                    c[2] - step * delta.z,
+                ];
+                let next_error = residual(next);
+                if next_error.length() < error.length() {
+                    break Some((next, next_error));
+                }
+                step *= 0.5;
+                if step < 1.0e-4 {
+                    break None;
+                }
+            };
+            let Some((next, next_error)) = improved else {
+                break;
+            };
+            c = next;
+            error = next_error;
+        }
+        c
+    }
+}
@@ -1,10 +1,10 @@
-use rand::random;
-
 use crate::onb::ONB;
+use crate::rand_vec3::random_unit_vector;
+use crate::sampler::random;
 use crate::{Float, Vec2, Vec3, PI};
 use std::fmt::{Debug, Write};
 
-use crate::hittable::{HitRecord, Hittable};
+use crate::hittable::{HitRecord, Hittable, SurfaceSample};
 use crate::interval::Interval;
 use crate::materials::material::Material;
 use crate::ray::Ray;
@@ -36,8 +36,8 @@ impl<'a> Sphere<'a> {
     }
 
     fn random_to_sphere(radius: Float, distance_squared: Float) -> Vec3 {
-        let r1 = random::<Float>();
-        let r2 = random::<Float>();
+        let r1 = random();
+        let r2 = random();
         let z = 1.0 + r2 * (Float::sqrt(1.0 - radius * radius / distance_squared) - 1.0);
 
         let phi = 2.0 * crate::PI * r1;
@@ -107,6 +107,37 @@ impl<'a> Hittable for Sphere<'a> {
         let uvw = ONB::new(&direction);
         uvw.transform(&Self::random_to_sphere(self.radius, distance_squared))
     }
+
+    fn sample_surface(&self) -> Option<SurfaceSample> {
+        let normal = random_unit_vector();
+        Some(SurfaceSample {
+            p: self.center + self.radius * normal,
+            normal,
+            pdf: 1.0 / (4.0 * PI * self.radius * self.radius),
+        })
+    }
+
+    fn surface_pdf_value(&self, origin: &Vec3, direction: &Vec3) -> Float {
+        if self
+            .hit(
+                &Ray::new(*origin, *direction),
+                Interval::new(0.0001, Float::MAX),
+            )
+            .is_some()
+        {
+            return 1.0 / (4.0 * PI * self.radius * self.radius);
+        }
+
+        0.0
+    }
+
+    fn emitters(&self) -> Vec<&dyn Hittable> {
+        if self.material.is_emissive() {
+            vec![self]
+        } else {
+            vec![]
+        }
+    }
 }
 
 impl<'a> Debug for Sphere<'a> {
@@ -1,3 +1,4 @@
+use crate::spectrum::{blackbody, blackbody_xyz};
 use crate::Float;
 
 use crate::{Vec2, Vec3};
@@ -23,6 +24,61 @@ impl Sampler2D for ColorTexture2D {
     }
 }
 
+#[derive(Default)]
+pub struct ColorTexture3D {
+    pub color: Color,
+}
+
+impl Sampler3D for ColorTexture3D {
+    fn sample(&self, _v: Vec3) -> Color {
+        self.color
+    }
+}
+
+// Turns temperatures in kelvin, read from the luminance of `temperature`, into the glow of a
+// black body. Brightness follows the physical radiance, reaching `intensity` at `max_temperature`.
+pub struct BlackbodyRamp<'a> {
+    temperature: &'a dyn Sampler3D,
+    max_temperature: Float,
+    ramp: Vec<Color>,
+}
+
+impl<'a> BlackbodyRamp<'a> {
+    const STEPS: usize = 256;
+
+    pub fn new(temperature: &'a dyn Sampler3D, max_temperature: Float, intensity: Float) -> Self {
+        let reference = blackbody_xyz(max_temperature).y;
+        let ramp = (0..=Self::STEPS)
+            .map(|i| {
+                let kelvin = max_temperature * i as Float / Self::STEPS as Float;
+                let xyz = blackbody_xyz(kelvin);
+                if reference <= 0.0 || xyz.y <= 0.0 {
+                    return Color::new(0.0, 0.0, 0.0);
+                }
+                blackbody(kelvin) * (intensity * xyz.y / reference)
+            })
+            .collect();
+        Self {
+            temperature,
+            max_temperature,
+            ramp,
+        }
+    }
+}
+
+impl<'a> Sampler3D for BlackbodyRamp<'a> {
+    fn sample(&self, v: Vec3) -> Color {
+        if self.max_temperature <= 0.0 {
+            return Color::new(0.0, 0.0, 0.0);
+        }
+        let x = (self.temperature.sample(v).luminance() / self.max_temperature).clamp(0.0, 1.0)
+            * Self::STEPS as Float;
+        let i = (x as usize).min(Self::STEPS - 1);
+        let t = x - i as Float;
+        self.ramp[i] * (1.0 - t) + self.ramp[i + 1] * t
+    }
+}
+
 pub struct ImageTexture2D {
     img: image::RgbImage,
 }
@@ -1,9 +1,11 @@
 use crate::{Vec2, Vec3};
 
+use crate::hittable::SurfaceSample;
 use crate::interval::Interval;
 use crate::ray::Ray;
+use crate::sampler::random;
 use crate::vertex::Vertex;
-use crate::Float;
+use crate::{Float, PI};
 
 pub struct Triangle {
     pub v0: Vertex,
@@ -53,4 +55,143 @@ impl Triangle {
             uv: tex_uv,
         });
     }
+
+    pub fn area(&self) -> Float {
+        0.5 * (self.v1.pos - self.v0.pos)
+            .cross(self.v2.pos - self.v0.pos)
+            .length()
+    }
+
+    pub fn geometric_normal(&self) -> Vec3 {
+        (self.v1.pos - self.v0.pos)
+            .cross(self.v2.pos - self.v0.pos)
+            .normalize()
+    }
+
+    // Two-sided intersection returning the ray parameter, used for light sampling densities.
+    pub fn intersect(&self, r: &Ray, ray_t: Interval) -> Option<Float> {
+        let v0v1 = self.v1.pos - self.v0.pos;
+        let v0v2 = self.v2.pos - self.v0.pos;
+        let pvec = r.direction.cross(v0v2);
+        let det = v0v1.dot(pvec);
+        if det.abs() < Float::EPSILON {
+            return None;
+        }
+        let inv_det = 1.0 / det;
+        let tvec = r.origin - self.v0.pos;
+        let u = tvec.dot(pvec) * inv_det;
+        let qvec = tvec.cross(v0v1);
+        let v = r.direction.dot(qvec) * inv_det;
+        if u < 0.0 || v < 0.0 || u + v > 1.0 {
+            return None;
+        }
+        let t = v0v2.dot(qvec) * inv_det;
+        ray_t.contains(t).then_some(t)
+    }
+
+    pub fn sample_area(&self) -> SurfaceSample {
+        let su0 = random().sqrt();
+        let b0 = 1.0 - su0;
+        let b1 = random() * su0;
+        SurfaceSample {
+            p: b0 * self.v0.pos + b1 * self.v1.pos + (1.0 - b0 - b1) * self.v2.pos,
+            normal: self.geometric_normal(),
+            pdf: 1.0 / self.area(),
+        }
+    }
+
+    // Solid angle subtended at `origin` (Van Oosterom and Strackee).
+    pub fn solid_angle(&self, origin: Vec3) -> Float {
+        let a = (self.v0.pos - origin).normalize();
+        let b = (self.v1.pos - origin).normalize();
+        let c = (self.v2.pos - origin).normalize();
+        let numerator = a.dot(b.cross(c)).abs();
+        let denominator = 1.0 + a.dot(b) + b.dot(c) + c.dot(a);
+        (2.0 * Float::atan2(numerator, denominator)).abs()
+    }
+
+    // Uniformly samples a direction from `origin` within the spherical triangle (Arvo).
+    pub fn sample_solid_angle(&self, origin: Vec3) -> Option<Vec3> {
+        let a = (self.v0.pos - origin).normalize();
+        let b = (self.v1.pos - origin).normalize();
+        let c = (self.v2.pos - origin).normalize();
+        let n_ab = a.cross(b).try_normalize()?;
+        let n_bc = b.cross(c).try_normalize()?;
+        let n_ca = c.cross(a).try_normalize()?;
+
+        let alpha = n_ab.angle_between(-n_ca);
+        let beta = n_bc.angle_between(-n_ab);
+        let gamma = n_ca.angle_between(-n_bc);
+        let area = alpha + beta + gamma - PI;
+        if area <= 0.0 {
+            return None;
+        }
+
+        // Pick the sub-triangle area, which fixes the vertex c' on the arc from a to c.
+        let sampled_area = random() * area + PI;
+        let (sin_alpha, cos_alpha) = alpha.sin_cos();
+        let (sin_area, cos_area) = sampled_area.sin_cos();
+        let sin_phi = sin_area * cos_alpha - cos_area * sin_alpha;
+        let cos_phi = cos_area * cos_alpha + sin_area * sin_alpha;
+        let k1 = cos_phi + cos_alpha;
+        let k2 = sin_phi - sin_alpha * a.dot(b);
+        let cos_b = ((k2 + (k2 * cos_phi - k1 * sin_phi) * cos_alpha)
+            / ((k2 * sin_phi + k1 * cos_phi) * sin_alpha))
+            .clamp(-1.0, 1.0);
+        let sin_b = (1.0 - cos_b * cos_b).max(0.0).sqrt();
+        let c_prime = cos_b * a + sin_b * (c - c.dot(a) * a).try_normalize()?;
+
+        // Then pick a point on the arc from b to c'.
+        let cos_theta = 1.0 - random() * (1.0 - c_prime.dot(b));
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        let direction =
+            cos_theta * b + sin_theta * (c_prime - c_prime.dot(b) * b).try_normalize()?;
+        direction.is_finite().then_some(direction)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pdf::tests::{assert_samples_match, integrate};
+
+    fn vertex(pos: Vec3) -> Vertex {
+        Vertex {
+            pos,
+            normal: Vec3::Z,
+            uv: Vec2::ZERO,
+        }
+    }
+
+    #[test]
+    fn solid_angle_samples_are_uniform() {
+        let triangle = Triangle {
+            v0: vertex(Vec3::new(-1.0, -0.5, 1.0)),
+            v1: vertex(Vec3::new(2.0, 0.0, 0.5)),
+            v2: vertex(Vec3::new(0.0, 1.5, 2.0)),
+        };
+        let origin = Vec3::new(0.1, 0.2, -0.4);
+        let (a, b, c) = (
+            triangle.v0.pos - origin,
+            triangle.v1.pos - origin,
+            triangle.v2.pos - origin,
+        );
+        let orientation = a.dot(b.cross(c)).signum();
+        let inside = |d: Vec3| {
+            d.dot(a.cross(b)) * orientation > 0.0
+                && d.dot(b.cross(c)) * orientation > 0.0
+                && d.dot(c.cross(a)) * orientation > 0.0
+        };
+
+        let solid_angle = triangle.solid_angle(origin);
+        let covered = integrate(|d| if inside(d) { 1.0 } else { 0.0 });
+        assert!(
+            (covered / solid_angle as f64 - 1.0).abs() < 1.0e-2,
+            "The triangle covers {} but has a solid angle of {}",
+            covered,
+            solid_angle
+        );
+        let pdf = |d: Vec3| if inside(d) { 1.0 / solid_angle } else { 0.0 };
+        assert_samples_match(|| triangle.sample_solid_angle(origin), pdf);
+    }
 }
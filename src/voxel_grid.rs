@@ -0,0 +1,74 @@
+use crate::{color::Color, texture::Sampler3D, Float, Vec3};
+
+// Scalar values on a regular grid filling the unit cube, such as the density of a simulated
+// cloud, looked up with trilinear interpolation between voxel centres.
+pub struct VoxelGrid {
+    resolution: [usize; 3],
+    values: Vec<Float>,
+    max_value: Float,
+}
+
+impl VoxelGrid {
+    // `values` holds `nx * ny * nz` voxels with x varying fastest, then y, then z.
+    pub fn new(nx: usize, ny: usize, nz: usize, values: Vec<Float>) -> Self {
+        assert!(nx * ny * nz == values.len() && !values.is_empty());
+        let max_value = values.iter().fold(0.0, |max: Float, v| max.max(*v));
+        Self {
+            resolution: [nx, ny, nz],
+            values,
+            max_value,
+        }
+    }
+
+    // Reads raw little-endian 32 bit floats laid out as for `new`.
+    pub fn from_raw(path: String, nx: usize, ny: usize, nz: usize) -> Self {
+        let bytes = std::fs::read(path).unwrap();
+        let values = bytes
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]) as Float)
+            .collect();
+        Self::new(nx, ny, nz, values)
+    }
+
+    // An upper bound of the interpolated values, used as the majorant for tracking.
+    pub fn max_value(&self) -> Float {
+        self.max_value
+    }
+
+    fn voxel(&self, x: usize, y: usize, z: usize) -> Float {
+        let [nx, ny, _] = self.resolution;
+        self.values[(z * ny + y) * nx + x]
+    }
+
+    pub fn value(&self, p: Vec3) -> Float {
+        if p.cmplt(Vec3::ZERO).any() || p.cmpgt(Vec3::ONE).any() {
+            return 0.0;
+        }
+        let [nx, ny, nz] = self.resolution;
+        // Lower voxel index and the fraction towards the next one along each axis.
+        let axis = |coordinate: Float, n: usize| {
+            let x = (coordinate * n as Float - 0.5).clamp(0.0, (n - 1) as Float);
+            let i = (x as usize).min(n.saturating_sub(2));
+            (i, (i + 1).min(n - 1), x - i as Float)
+        };
+        let (x0, x1, tx) = axis(p.x, nx);
+        let (y0, y1, ty) = axis(p.y, ny);
+        let (z0, z1, tz) = axis(p.z, nz);
+        let lerp = |a: Float, b: Float, t: Float| a + (b - a) * t;
+        let plane = |z: usize| {
+            lerp(
+                lerp(self.voxel(x0, y0, z), self.voxel(x1, y0, z), tx),
+                lerp(self.voxel(x0, y1, z), self.voxel(x1, y1, z), tx),
+                ty,
+            )
+        };
+        lerp(plane(z0), plane(z1), tz)
+    }
+}
+
+impl Sampler3D for VoxelGrid {
+    fn sample(&self, v: Vec3) -> Color {
+        let value = self.value(v);
+        Color::new(value, value, value)
+    }
+}